@@ -7,24 +7,40 @@
 
 use core::{ptr, f32::consts::PI};
 use alloc::sync::Arc;
-use alloc::{format, vec};
-use bevy_ecs::query::{With, WorldQuery};
+use alloc::{format, vec, vec::Vec};
+use bevy_ecs::query::{With, Without, WorldQuery};
 use bevy_ecs::resource::Resource;
 use bevy_ecs::schedule::{IntoScheduleConfigs, Schedule};
-use bevy_ecs::system::{Commands, Query, Res, ResMut, Single};
+use bevy_ecs::removal_detection::RemovedComponents;
+use bevy_ecs::system::{Commands, Query, Res, ResMut, Single, SystemParam};
+use bevy_ecs::change_detection::{DetectChanges, DetectChangesMut, Ref};
+use hashbrown::{HashMap, HashSet};
 use bevy_ecs::world::World;
-use psp::Align16;
 use psp::sys::{
-    self, sceGuBlendFunc, sceGuEnable, ClearBuffer, CtrlButtons, DepthFunc, DisplayPixelFormat, FrontFaceDirection, GuContextType, GuPrimitive, GuState, GuSyncBehavior, GuSyncMode, MipmapLevel, ScePspFVector3, ShadingModel, TextureColorComponent, TextureEffect, TextureFilter, TexturePixelFormat, VertexType
+    self, ClearBuffer, CtrlButtons, DepthFunc, DisplayPixelFormat, FrontFaceDirection, GuPrimitive, GuState, GuSyncBehavior, GuSyncMode, ScePspFMatrix4, ScePspFVector3, ShadingModel, TextureColorComponent, TextureEffect, TexturePixelFormat, VertexType
 };
-use psp::vram_alloc::get_vram_allocator;
 use psp::{BUF_WIDTH, SCREEN_WIDTH, SCREEN_HEIGHT};
 use bevy_ecs::component;
 
 extern crate alloc;
 
 use psp_assets::{Asset, AssetServer, Font, Image};
-use psp_geometry::{Material, Mesh};
+use psp_geometry::{AlphaMode, Lod, Material, Mesh, MorphAnimation, MorphKey, MorphWeights, Precision, TextureMapping, VertexLayout};
+use psp_math::Frustum;
+use psp_debug::{DebugDraw, StatsOverlay};
+use psp_light::DirectionalLight;
+use psp_particles::ParticleEmitter;
+use psp_post::{Bloom, PostProcess, Vignette};
+use psp_screenshot::Screenshot;
+use psp_toon::Toon;
+use psp_shadow::CastsShadow;
+use psp_sky::Skybox;
+use psp_sprite::{draw_sprites, Sprite};
+use psp_text::{FontMetrics, Text, TextAlign, TextFont};
+use psp_render::{
+    begin_2d, bind_viewport, end_2d, sphere_map_axes, BlendMode, CallList, Camera, ClearColor, ClearFlags, Culling, DisplayConfig, DisplayLists, Fog, Framebuffers, GroupList, RenderState, RenderTarget,
+    StaticDraw, StaticGroup, StaticGroups, Vram, SPHERE_MAP_LIGHTS
+};
 use spin::Once;
 
 mod psp_image;
@@ -33,17 +49,28 @@ mod psp_input;
 mod psp_print;
 mod psp_math;
 mod psp_text;
+mod psp_sprite;
+mod psp_debug;
+mod psp_sky;
+mod psp_particles;
+mod psp_light;
+mod psp_shadow;
+mod psp_post;
+mod psp_toon;
+mod psp_screenshot;
 mod psp_assets;
+mod psp_render;
 use psp_image::{load_png, load_png_swizzled};
 
 psp::module!("ESO", 1, 1);
 
-static mut LIST: Align16<[u32; 0x40000]> = Align16([0; 0x40000]);
-
 // Game constants
 const PLAYER_SPEED: f32 = 2.5;
 const CAMERA_ROTATION_SPEED: f32 = PI;
 
+// Level geometry never moves, so it is recorded once into a single call list
+const LEVEL_GEOMETRY: StaticGroup = StaticGroup(0);
+
 #[derive(Debug, component::Component)]
 struct Transform{
     translation: ScePspFVector3,
@@ -209,19 +236,94 @@ fn update_player(mut transform: Single<&mut Transform, With<Player>>, time: Res<
     }
 }
 
+fn update_particles(mut emitters: Query<(&Transform, &mut ParticleEmitter)>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    for (transform, mut emitter) in emitters.iter_mut() {
+        let t = transform.translation;
+        emitter.update([t.x, t.y, t.z], dt);
+    }
+}
+
+fn update_stats_overlay(mut stats: ResMut<StatsOverlay>, controller: Res<Controller>) {
+    stats.handle_input(controller.buttons);
+}
+
+/// Age shapes once they've been drawn, so whatever was pushed during update shows at least once
+fn expire_debug_draw(mut debug: ResMut<DebugDraw>, time: Res<Time>) {
+    debug.tick(time.delta_seconds());
+}
+
+/// World axes and the bounds of everything with a mesh
+fn draw_debug_gizmos(mut debug: ResMut<DebugDraw>, meshes: Query<(&Mesh, &Transform)>) {
+    if !debug.enabled {
+        return;
+    }
+
+    debug.axes([0.0, 0.0, 0.0], 1.0);
+    for (mesh, transform) in meshes.iter() {
+        let t = transform.translation;
+        let corners = mesh.aabb.corners().map(|c| {
+            let c = psp_math::rotate_xyz(c, &transform.rotation);
+            [c[0] + t.x, c[1] + t.y, c[2] + t.z]
+        });
+        debug.box_corners(&corners, 0xFF00FFFF);
+    }
+}
+
+fn update_post(mut post: ResMut<PostProcess>, time: Res<Time>) {
+    post.tick(time.delta_seconds());
+}
+
+fn update_morphs(mut morphs: Query<&mut MorphWeights>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    for mut morph in morphs.iter_mut() {
+        // Only flag a change when the weights move, so finished animations don't dirty recordings
+        if morph.bypass_change_detection().tick(dt) {
+            morph.set_changed();
+        }
+    }
+}
+
+/// Track the level the player's view uses, which is what static recordings are made with.
+/// `draw_view` picks levels for every view itself.
+fn update_lod(camera: Single<&Transform, With<Player>>, mut lods: Query<(&Transform, &mut Lod)>) {
+    for (transform, mut lod) in lods.iter_mut() {
+        let (a, b) = (transform.translation, camera.translation);
+        let distance = psp_math::vfpu_sqrtf(psp_math::distance_squared([a.x, a.y, a.z], [b.x, b.y, b.z]));
+
+        // Only touch the component on an actual switch so cached recordings stay valid
+        let level = lod.select(distance);
+        if level != lod.current() {
+            lod.set_current(level);
+        }
+    }
+}
+
 #[allow(non_snake_case)]
-fn init_Gu() {
+fn init_Gu(
+    mut lists: ResMut<DisplayLists>,
+    vram: Res<Vram>,
+    config: Res<DisplayConfig>,
+    mut framebuffers: ResMut<Framebuffers>
+) {
     unsafe {
         psp::enable_home_button();
 
-        let allocator = get_vram_allocator().unwrap();
-        let fbp0 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888);
-        let fbp1 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888);
-        let zbp = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm4444);
-        // Attempting to free the three VRAM chunks at this point would give a
-        // compile-time error since fbp0, fbp1 and zbp are used later on
-        //allocator.free_all();
-        
+        // The allocator stays in the Vram resource so render targets can be carved out later.
+        // The depth buffer is always 16-bit, allocated here as a 16-bit texture of the same size.
+        let (fbp0, _) = vram.alloc_texture(BUF_WIDTH, SCREEN_HEIGHT, config.texture_format());
+        let (fbp1, _) = vram.alloc_texture(BUF_WIDTH, SCREEN_HEIGHT, config.texture_format());
+        let zbp = match config.depth {
+            true => vram.alloc_texture(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm4444).0,
+            false => ptr::null_mut(),
+        };
+        *framebuffers = Framebuffers {
+            draw: fbp0 as _,
+            display: fbp1 as _,
+            depth: zbp as _,
+            format: config.format,
+        };
+
 
         // Load identity matrix into Gu
         sys::sceGumLoadIdentity();
@@ -230,21 +332,21 @@ fn init_Gu() {
         sys::sceGuInit();
 
         // Setup Gu for 3d
-        sys::sceGuStart(GuContextType::Direct, &raw mut LIST.0 as *mut [u32; 0x40000] as *mut _);
-        sys::sceGuDrawBuffer(DisplayPixelFormat::Psm8888, fbp0.as_mut_ptr_from_zero() as _, BUF_WIDTH as i32);
-        sys::sceGuDispBuffer(SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32, fbp1.as_mut_ptr_from_zero() as _, BUF_WIDTH as i32);
-        sys::sceGuDepthBuffer(zbp.as_mut_ptr_from_zero() as _, BUF_WIDTH as i32);
-        sys::sceGuOffset(2048 - (SCREEN_WIDTH / 2), 2048 - (SCREEN_HEIGHT / 2));
-        sys::sceGuViewport(2048, 2048, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
-        sys::sceGuDepthRange(65535, 0);
-        sys::sceGuScissor(0, 0, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+        lists.start(0);
+        sys::sceGuDrawBuffer(config.format, fbp0 as _, BUF_WIDTH as i32);
+        sys::sceGuDispBuffer(SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32, fbp1 as _, BUF_WIDTH as i32);
+        bind_viewport(SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
         sys::sceGuEnable(GuState::ScissorTest);
-        sys::sceGuDepthFunc(DepthFunc::Greater);
-        sys::sceGuEnable(GuState::DepthTest);
+        if config.depth {
+            sys::sceGuDepthBuffer(zbp as _, BUF_WIDTH as i32);
+            sys::sceGuDepthRange(65535, 0);
+            sys::sceGuDepthFunc(DepthFunc::Greater);
+            sys::sceGuEnable(GuState::DepthTest);
+        }
+        config.apply_dither();
         sys::sceGuShadeModel(ShadingModel::Smooth);
         sys::sceGuEnable(GuState::CullFace);
         sys::sceGuFrontFace(FrontFaceDirection::Clockwise);
-        sys::sceGuEnable(GuState::Texture2D);
         sys::sceGuEnable(GuState::ClipPlanes);
         sys::sceGuFinish();
         sys::sceGuSync(GuSyncMode::Finish, GuSyncBehavior::Wait);
@@ -252,126 +354,678 @@ fn init_Gu() {
         psp::sys::sceDisplayWaitVblankStart();
 
         sys::sceGuDisplay(true);
+
+        DisplayLists::install();
     }
 }
 
-fn clear_screen() {
-    unsafe {
-        // clear screen
-        sys::sceGuClearColor(0xff554433);
-        sys::sceGuClearDepth(0);
-        sys::sceGuClear(ClearBuffer::COLOR_BUFFER_BIT | ClearBuffer::DEPTH_BUFFER_BIT);
+fn clear_screen(
+    clear_color: Res<ClearColor>,
+    fog: Res<Fog>,
+    camera: Single<(Option<&Camera>, Option<&Fog>), With<Player>>,
+    config: Res<DisplayConfig>
+) {
+    let (camera, camera_fog) = *camera;
+    let default_camera = Camera::default();
+    let camera = camera.unwrap_or(&default_camera);
+    let color = camera.background(&clear_color, camera_fog.unwrap_or(&fog));
+
+    unsafe { camera.clear.clear(color, &config) };
+}
+
+
+/// Whether an entity's bounding sphere is at least partly inside the frustum
+fn is_visible(frustum: &Frustum, mesh: &Mesh, transform: &Transform) -> bool {
+    let c = psp_math::rotate_xyz(mesh.sphere.center, &transform.rotation);
+    let t = transform.translation;
+    frustum.intersects_sphere([c[0] + t.x, c[1] + t.y, c[2] + t.z], mesh.sphere.radius)
+}
+
+/// Send the blend weights for a mesh's morph targets. Without `morph` only the first target shows.
+unsafe fn apply_morph(mesh: &Mesh, morph: Option<&MorphWeights>) {
+    if mesh.morph_targets <= 1 {
+        return;
+    }
+    let default = MorphWeights::default();
+    let weights = &morph.unwrap_or(&default).weights;
+    for (i, &weight) in weights.iter().enumerate().take(mesh.morph_targets) {
+        sys::sceGuMorphWeight(i as i32, weight);
     }
 }
 
+/// Set up material state and draw a single mesh. With `ge_cull` the GE tests the mesh's bounding
+/// box first and skips the draw if it is off screen.
+unsafe fn draw_mesh(state: &mut RenderState, mesh: &Mesh, transform: &Transform, material: &Material, morph: Option<&MorphWeights>, ge_cull: bool) {
+    // Vertex format comes from the mesh's layout, index bits included
+    let vertex_type = mesh.vertex_type() | VertexType::TRANSFORM_3D;
 
-fn render_world(query: Query<(&Mesh, &Transform, &Material)>) {
-    unsafe {
-        
-        // Setup matrices for rendering
-        sys::sceGumMatrixMode(sys::MatrixMode::Projection);
+    // Texture state is derived from this material alone so nothing leaks over from the last draw
+    let texture = match &material.handle {
+        Some(handle) => {
+            let texture = handle.upgrade();
+            if texture.is_none() {
+                state.stats.missing_textures += 1;
+            }
+            texture
+        },
+        None => None
+    };
+
+    // Sphere mapping works from normals, so without them the mesh falls back to its UVs
+    let mapping = match material.mapping {
+        TextureMapping::SphereMap if mesh.layout.normal.is_none() => TextureMapping::Uv,
+        mapping => mapping,
+    };
+
+    // Toon shading works from normals too, and draws like a plain material without them
+    let toon = material.toon.as_ref().filter(|_| mesh.layout.normal.is_some());
+
+    match (toon, texture) {
+        (Some(toon), _) => psp_toon::bind(state, toon, material.color),
+        // A texture is only sampled if the mesh actually carries UVs, or generates them
+        (None, Some(texture)) if mapping == TextureMapping::SphereMap || mesh.layout.texture.is_some() => {
+            // Setup Texture
+            // Textures need to be swizzled
+            state.set(GuState::Texture2D, true);
+            state.bind_texture(&texture, material.texture_format, material.swizzle);
+            state.tex_map(match mapping {
+                TextureMapping::Uv => None,
+                TextureMapping::SphereMap => Some(SPHERE_MAP_LIGHTS),
+            });
+            state.tex_wrap(true);
+
+            // Vertex colored meshes tint their texture instead of being replaced by it
+            let tfx = if mesh.layout.color.is_some() { TextureEffect::Modulate } else { TextureEffect::Replace };
+            state.tex_func(tfx, TextureColorComponent::Rgba);
+        },
+        _ => {
+            // Solid color, or the mesh's vertex colors if it has them
+            state.set(GuState::Texture2D, false);
+            state.color(material.color);
+        }
+    }
+
+    match material.alpha_mode {
+        AlphaMode::Opaque => {
+            state.alpha_test(None);
+            state.blend(None);
+            state.depth_write(true);
+        }
+        AlphaMode::Mask(cutoff) => {
+            state.alpha_test(Some(cutoff));
+            state.blend(None);
+            state.depth_write(true);
+        }
+        AlphaMode::Blend => {
+            state.alpha_test(None);
+            state.blend(Some(BlendMode::Alpha));
+            // Blended surfaces still test against depth, but must not hide what is behind them
+            state.depth_write(false);
+        }
+    }
+    
+    // Place mesh
+    sys::sceGumLoadIdentity();
+    sys::sceGumTranslate(&transform.translation);
+    sys::sceGumRotateXYZ(&transform.rotation);
+
+    // Compressed positions are stored normalized, scale them back up
+    if mesh.scale != 1.0 {
+        sys::sceGumScale(&ScePspFVector3 { x: mesh.scale, y: mesh.scale, z: mesh.scale });
+    }
+    
+    // See if mesh was created with indices or full vertex descriptions
+    let ind = match &mesh.indices {
+        Some(p) => p.as_ptr() as *const _,
+        None => ptr::null_mut()
+    };
+
+    if ge_cull {
+        // The bounding box is tested against the model matrix, so it has to be sent first
+        sys::sceGumUpdateMatrix();
+        sys::sceGuBeginObject(
+            (VertexType::VERTEX_32BITF | VertexType::TRANSFORM_3D).bits(),
+            8,
+            ptr::null(),
+            mesh.bounding_box.as_ptr() as *const _
+        );
+    }
+
+    // draw mesh
+    apply_morph(mesh, morph);
+    state.draw(
+        mesh.primitive_type,
+        vertex_type,
+        mesh.draw_count(),
+        ind,
+        mesh.vertices.as_ptr() as *const _
+    );
+
+    if let Some(outline) = toon.and_then(|t| t.outline.as_ref()) {
         sys::sceGumLoadIdentity();
-        // Fov, Aspect Ratio, Near clipping field, far clipping field
-        sys::sceGumPerspective(90.0, 16.0 / 9.0, 0.50, 40.0);
+        sys::sceGumTranslate(&transform.translation);
+        sys::sceGumRotateXYZ(&transform.rotation);
+        psp_toon::draw_outline(state, outline, mesh, ind);
+    }
+
+    if ge_cull {
+        sys::sceGuEndObject();
+    }
+}
+
+/// Mark cached lists dirty when anything they were recorded from changes
+fn invalidate_static_draws(
+    mut draws: Query<(Ref<Mesh>, Ref<Transform>, Ref<Material>, Option<Ref<Lod>>, Option<Ref<MorphWeights>>, &mut StaticDraw)>,
+    members: Query<(Ref<Mesh>, Ref<Transform>, Ref<Material>, Option<Ref<Lod>>, Option<Ref<MorphWeights>>, Ref<StaticGroup>)>,
+    mut removed: RemovedComponents<StaticGroup>,
+    mut groups: ResMut<StaticGroups>,
+    culling: Res<Culling>,
+) {
+    // A texture that was unloaded after recording leaves the list pointing at freed memory
+    let missing = |material: &Material| {
+        material.handle.as_ref().is_some_and(|h| h.strong_count() == 0) as u32
+    };
+
+    // Switching detail level swaps the mesh that was recorded, and morph weights are baked in
+    let lod_changed = |lod: &Option<Ref<Lod>>| lod.as_ref().is_some_and(|l| l.is_changed());
+    let morph_changed = |morph: &Option<Ref<MorphWeights>>| morph.as_ref().is_some_and(|m| m.is_changed());
+
+    // Recordings bake in whether draws are wrapped in GE bounding box tests
+    let all = culling.is_changed();
+
+    for (mesh, transform, material, lod, morph, mut draw) in draws.iter_mut() {
+        let changed = mesh.is_changed() || transform.is_changed() || material.is_changed() || lod_changed(&lod) || morph_changed(&morph);
+        if all || changed || missing(&material) > draw.missing_textures {
+            draw.dirty = true;
+        }
+    }
+
+    // Nothing records which group a despawned entity belonged to, so start over
+    if all || removed.read().next().is_some() {
+        groups.invalidate_all();
+    }
 
-        // Have set load the identity matrix into the model matrix so that the model we spawn isn't
-        // at some "random" orientation/permutation
+    let mut missing_per_group = HashMap::<StaticGroup, u32>::new();
+    for (mesh, transform, material, lod, morph, group) in members.iter() {
+        if mesh.is_changed() || transform.is_changed() || material.is_changed() || lod_changed(&lod) || morph_changed(&morph) || group.is_changed() {
+            groups.invalidate(*group);
+        }
+        *missing_per_group.entry(*group).or_default() += missing(&material);
+    }
+    for (group, missing) in missing_per_group {
+        if groups.lists.get(&group).is_some_and(|g| missing > g.missing_textures) {
+            groups.invalidate(group);
+        }
+    }
+}
+
+/// Record the call lists of any static draws or groups that don't have an up to date one
+fn record_static_draws(
+    mut draws: Query<(&Mesh, &Transform, &Material, Option<&Lod>, Option<&MorphWeights>, &mut StaticDraw)>,
+    members: Query<(&Mesh, &Transform, &Material, Option<&Lod>, Option<&MorphWeights>, &StaticGroup)>,
+    mut groups: ResMut<StaticGroups>,
+    culling: Res<Culling>,
+    config: Res<DisplayConfig>,
+) {
+    unsafe {
+        // Flush view and projection into the frame's list so they don't get baked into a recording
+        sys::sceGumUpdateMatrix();
         sys::sceGumMatrixMode(sys::MatrixMode::Model);
-        sys::sceGumLoadIdentity();
 
-        let mut vertex_type = VertexType::VERTEX_32BITF | VertexType::TRANSFORM_3D;
-        
-        for (mesh, transform, material) in query.iter() {
-            if let Some(handle) = &material.handle {
-                if let Some(s_handle) = handle.upgrade() {
-                    let w = s_handle.width();
-                    let h = s_handle.height();
-                    let pitch_px = s_handle.pitch();
-                    let swizzle = material.swizzle as i32; 
-
-                    if material.blend {
-                        sceGuEnable(GuState::Blend);
-                        sceGuBlendFunc(sys::BlendOp::Add,  sys::BlendFactor::SrcAlpha, sys::BlendFactor::OneMinusSrcAlpha, 0, 0);
-                    }
-                    
-                    // Setup Texture
-                    // Textures need to be swizzled
-                    sys::sceGuTexMode(TexturePixelFormat::Psm8888, 0, 0, swizzle);
-                    sys::sceGuTexImage(MipmapLevel::None, w as i32, h as i32, pitch_px as i32, s_handle.raw_bytes());
-                    sys::sceGuTexFunc(TextureEffect::Replace, TextureColorComponent::Rgba); // Texture Function
-                    sys::sceGuTexFilter(TextureFilter::Linear, TextureFilter::Linear); // Texture filtering
-                    sys::sceGuTexScale(1.0, 1.0); // Texture scale
-                    sys::sceGuTexOffset(0.0, 0.0); // Texture offset
-                   
-                    // Indicate that the next render will include a texture
-                    vertex_type.set(VertexType::TEXTURE_32BITF, true);
+        // Recordings start from an unknown GE state, so each one sets up everything it needs
+        for (mesh, transform, material, lod, morph, mut draw) in draws.iter_mut() {
+            if draw.is_ready() || material.is_transparent() {
+                continue;
+            }
+            let mesh = lod.map_or(mesh, |l| l.mesh(mesh));
+
+            let mut list = draw.list.take().unwrap_or_else(|| CallList::with_draws(1));
+            let mut recording = RenderState::for_display(&config);
+            list.record(|list| {
+                // A retry into a bigger list starts from scratch
+                recording = RenderState::for_display(&config);
+                if !list.reserve(CallList::WORDS_PER_DRAW) {
+                    return;
                 }
-            };
-            
-            // Set to model manipulation mode
-            sys::sceGumMatrixMode(sys::MatrixMode::Model);
-            sys::sceGumLoadIdentity();
-            
-            // Place mesh
-            sys::sceGumTranslate(&transform.translation);
-            sys::sceGumRotateXYZ(&transform.rotation);
-            
-            // See if mesh was created with indices or full vertex descriptions
-            let ind = match &mesh.indices {
-                // If it was created with indices, use them
-                Some(p) => {
-                    vertex_type.set(VertexType::INDEX_16BIT, true);
-                    p.as_ptr() as *const _
-                },
-                // Else, make sure we unset the bit value
-                None => {
-                    vertex_type.set(VertexType::INDEX_16BIT, false);
-                    ptr::null_mut()
+                recording.tex_filter(true);
+                recording.tex_transform(1.0, 1.0, 0.0, 0.0);
+                draw_mesh(&mut recording, mesh, transform, material, morph, culling.ge_bounding_box);
+            });
+
+            draw.list = Some(list);
+            draw.dirty = false;
+            draw.missing_textures = recording.stats.missing_textures;
+        }
+
+        let mut pending = HashMap::<StaticGroup, Vec<_>>::new();
+        for (mesh, transform, material, lod, morph, group) in members.iter() {
+            if !groups.is_ready(*group) && !material.is_transparent() {
+                let mesh = lod.map_or(mesh, |l| l.mesh(mesh));
+                pending.entry(*group).or_default().push((mesh, transform, material, morph));
+            }
+        }
+
+        for (group, mut items) in pending {
+            items.sort_by_key(|(_, _, material, _)| material.sort_key());
+
+            let mut list = CallList::with_draws(items.len());
+            let mut recording = RenderState::for_display(&config);
+            list.record(|list| {
+                // A retry into a bigger list starts from scratch
+                recording = RenderState::for_display(&config);
+                recording.tex_filter(true);
+                recording.tex_transform(1.0, 1.0, 0.0, 0.0);
+                for &(mesh, transform, material, morph) in &items {
+                    if !list.reserve(CallList::WORDS_PER_DRAW) {
+                        return;
+                    }
+                    draw_mesh(&mut recording, mesh, transform, material, morph, culling.ge_bounding_box);
                 }
+            });
+
+            groups.lists.insert(group, GroupList { list, missing_textures: recording.stats.missing_textures });
+        }
+    }
+}
+
+/// Everything `render_world` draws, shared by every camera
+#[derive(SystemParam)]
+struct Scene<'w, 's> {
+    query: Query<'w, 's, (&'static Mesh, &'static Transform, &'static Material, Option<&'static StaticDraw>, Option<&'static StaticGroup>, Option<&'static Lod>, Option<&'static MorphWeights>)>,
+    casters: Query<'w, 's, (&'static Mesh, &'static Transform, &'static CastsShadow, Option<&'static Lod>, Option<&'static MorphWeights>)>,
+    lights: Query<'w, 's, &'static DirectionalLight>,
+    sky: Option<Res<'w, Skybox>>,
+    fog: Res<'w, Fog>,
+    groups: Res<'w, StaticGroups>,
+}
+
+/// Build the view matrix for a camera placed by `transform`
+fn view_matrix(transform: &Transform) -> ScePspFMatrix4 {
+    unsafe {
+        // The inverse of the model matrix draw_mesh would build from the same transform
+        let r = transform.rotation;
+        let t = transform.translation;
+        sys::sceGumMatrixMode(sys::MatrixMode::View);
+        sys::sceGumLoadIdentity();
+        sys::sceGumRotateZ(-r.z);
+        sys::sceGumRotateY(-r.y);
+        sys::sceGumRotateX(-r.x);
+        sys::sceGumTranslate(&ScePspFVector3 { x: -t.x, y: -t.y, z: -t.z });
+
+        let mut view: ScePspFMatrix4 = core::mem::zeroed();
+        sys::sceGumStoreMatrix(&mut view);
+        view
+    }
+}
+
+/// Load a camera's projection and view, and the frustum culling and world-space passes work from
+unsafe fn set_view(culling: &mut Culling, camera: &Camera, view: &ScePspFMatrix4) {
+    // Setup matrices for rendering
+    sys::sceGumMatrixMode(sys::MatrixMode::Projection);
+    sys::sceGumLoadIdentity();
+    // Fov, Aspect Ratio, Near clipping field, far clipping field
+    sys::sceGumPerspective(camera.fov, camera.aspect(), camera.near, camera.far);
+    sys::sceGumMatrixMode(sys::MatrixMode::View);
+    sys::sceGumLoadMatrix(view);
+
+    // Build the frustum from the projection and view
+    let mut projection: ScePspFMatrix4 = core::mem::zeroed();
+    sys::sceGumMatrixMode(sys::MatrixMode::Projection);
+    sys::sceGumStoreMatrix(&mut projection);
+    culling.view_projection = psp_math::mat4_mul(&projection, view);
+    culling.view = *view;
+    culling.frustum = Frustum::from_matrix(&culling.view_projection);
+
+    // Everything from here on only touches the model matrix
+    sys::sceGumMatrixMode(sys::MatrixMode::Model);
+}
+
+/// Draw the scene once from one camera into whatever draw buffer is bound
+unsafe fn draw_view(
+    scene: &Scene,
+    state: &mut RenderState,
+    lists: &mut DisplayLists,
+    culling: &mut Culling,
+    camera: &Camera,
+    view: &ScePspFMatrix4,
+    eye: &ScePspFVector3,
+    camera_fog: Option<&Fog>,
+) {
+    set_view(culling, camera, view);
+
+    // The sky goes first so everything else draws over it, and is never fogged itself
+    if let Some(sky) = &scene.sky {
+        lists.reserve(CallList::WORDS_PER_DRAW * 6);
+        state.fog(&Fog::default());
+        sky.draw(state, eye);
+    }
+    state.fog(camera_fog.unwrap_or(&scene.fog));
+
+    // Cached lists don't carry view or projection, so those have to be sent beforehand
+    sys::sceGumUpdateMatrix();
+    sphere_map_axes(view);
+    psp_toon::set_light(scene.lights.iter().next().map_or(DirectionalLight::default().direction, |l| l.direction));
+
+    // Transparent draws are sorted by it, and detail levels are picked from it
+    let distance = |t: &Transform| {
+        let dx = t.translation.x - eye.x;
+        let dy = t.translation.y - eye.y;
+        let dz = t.translation.z - eye.z;
+        dx * dx + dy * dy + dz * dz
+    };
+
+    // Each view picks its own detail level. Recordings hold the level the player's view picked
+    // in `update_lod`, so they're only replayed where this view agrees with it.
+    let level = |transform: &Transform, lod: &Lod| lod.select(psp_math::vfpu_sqrtf(distance(transform)));
+    let other_level = |transform: &Transform, lod: Option<&Lod>| lod.is_some_and(|l| level(transform, l) != l.current());
+    let stale_groups: HashSet<StaticGroup> = scene.query
+        .iter()
+        .filter(|(_, transform, _, _, _, lod, _)| other_level(transform, *lod))
+        .filter_map(|(_, _, _, _, group, _, _)| group.copied())
+        .collect();
+
+    let mut cached = 0;
+    for (_, transform, material, draw, _, lod, _) in scene.query.iter() {
+        let draw = draw.filter(|d| d.is_ready() && !material.is_transparent() && !other_level(transform, lod));
+        if let Some(draw) = draw {
+            lists.reserve(4);
+            draw.list.as_ref().unwrap().call();
+            cached += 1;
+        }
+    }
+    for (_, group) in scene.groups.lists.iter().filter(|(g, _)| !stale_groups.contains(*g)) {
+        lists.reserve(4);
+        group.list.call();
+        cached += 1;
+    }
+    if cached > 0 {
+        // The replayed lists changed GE state behind the cache's back
+        state.invalidate();
+        state.stats.call_lists += cached;
+    }
+
+    // Drop everything off screen before any of it reaches the GE
+    let frustum = culling.frustum;
+    let frustum_culling = culling.frustum_culling;
+    let (mut visible, mut culled) = (0, 0);
+
+    // Split the rest into an opaque pass (opaque and cutout materials) and a transparent pass
+    let (mut opaque, mut transparent): (Vec<_>, Vec<_>) = scene.query
+        .iter()
+        .filter(|(_, transform, material, draw, group, lod, _)| {
+            let group_ready = group.is_some_and(|g| scene.groups.is_ready(*g) && !stale_groups.contains(g));
+            let recorded = (draw.is_some_and(|d| d.is_ready()) && !other_level(transform, *lod)) || group_ready;
+            material.is_transparent() || !recorded
+        })
+        .map(|(mesh, transform, material, _, _, lod, morph)| {
+            (lod.map_or(mesh, |l| l.level_mesh(level(transform, l), mesh)), transform, material, morph)
+        })
+        .filter(|(mesh, transform, _, _)| {
+            let keep = !frustum_culling || is_visible(&frustum, mesh, transform);
+            if keep { visible += 1 } else { culled += 1 }
+            keep
+        })
+        .partition(|(_, _, material, _)| !material.is_transparent());
+
+    state.stats.visible += visible;
+    state.stats.culled += culled;
+
+    // Opaque draws are sorted by material so consecutive draws can share texture state
+    opaque.sort_by_key(|(_, _, material, _)| material.sort_key());
+
+    // Transparent draws have to be composited furthest first
+    transparent.sort_by(|(_, a, _, _), (_, b, _, _)| distance(b).total_cmp(&distance(a)));
+
+    // These are the same for every material for now, so they only get sent once
+    state.tex_filter(true);
+    state.tex_transform(1.0, 1.0, 0.0, 0.0);
+    
+    for (mesh, transform, material, morph) in opaque {
+        lists.reserve(CallList::WORDS_PER_DRAW);
+        draw_mesh(state, mesh, transform, material, morph, culling.ge_bounding_box);
+    }
+
+    // Shadows land on the opaque ground, and go under anything transparent
+    if let Some(light) = scene.lights.iter().next() {
+        let mut casters = scene.casters.iter().peekable();
+        if casters.peek().is_some() {
+            psp_shadow::begin_shadows(state);
+            for (mesh, transform, shadow, lod, morph) in casters {
+                let mesh = lod.map_or(mesh, |l| l.level_mesh(level(transform, l), mesh));
+                apply_morph(mesh, morph);
+                psp_shadow::draw_shadow(state, lists, shadow, mesh, &transform.translation, &transform.rotation, light.direction);
+            }
+            psp_shadow::end_shadows(state);
+        }
+    }
+
+    for (mesh, transform, material, morph) in transparent {
+        lists.reserve(CallList::WORDS_PER_DRAW);
+        draw_mesh(state, mesh, transform, material, morph, culling.ge_bounding_box);
+    }
+}
+
+fn render_world(
+    scene: Scene,
+    camera: Single<(&Transform, Option<&Camera>, Option<&Fog>), With<Player>>,
+    cameras: Query<(&Transform, &Camera, Option<&Fog>), Without<Player>>,
+    framebuffers: Res<Framebuffers>,
+    clear_color: Res<ClearColor>,
+    config: Res<DisplayConfig>,
+    mut state: ResMut<RenderState>,
+    mut lists: ResMut<DisplayLists>,
+    mut culling: ResMut<Culling>
+) {
+    unsafe {
+        // Hold on to the view update_player set, other cameras overwrite it
+        let mut player_view: ScePspFMatrix4 = core::mem::zeroed();
+        sys::sceGumMatrixMode(sys::MatrixMode::View);
+        sys::sceGumStoreMatrix(&mut player_view);
+
+        // Render targets first, so the screen can sample them this frame
+        let mut offscreen_drawn = false;
+        for (transform, camera, camera_fog) in cameras.iter() {
+            let Some(target) = &camera.target else {
+                continue;
             };
 
-            // draw cube
-            sys::sceGumDrawArray(
-                mesh.primitive_type,
-                VertexType::from_bits_retain(vertex_type.bits()),
-                mesh.vertices.len() as i32,
-                ind,
-                mesh.vertices.as_ptr() as *const _
-            );
-
-            if material.blend {
-                sys::sceGuDisable(GuState::Blend);
+            lists.reserve(32);
+            target.bind();
+            // Whatever the last view left in the shared depth buffer was laid out for its stride
+            let clear = ClearFlags { depth: true, ..camera.clear };
+            clear.clear(camera.background(&clear_color, camera_fog.unwrap_or(&scene.fog)), &config);
+            draw_view(&scene, &mut state, &mut lists, &mut culling, camera, &view_matrix(transform), &transform.translation, camera_fog);
+            offscreen_drawn = true;
+        }
+
+        let (transform, camera, camera_fog) = *camera;
+        let default_camera = Camera::default();
+        let camera = camera.unwrap_or(&default_camera);
+
+        if offscreen_drawn {
+            // Targets share the depth buffer, so the screen's has to be cleared again
+            lists.reserve(32);
+            framebuffers.bind();
+            if camera.clear.depth {
+                ClearFlags::DEPTH.clear(0, &config);
             }
         }
+        draw_view(&scene, &mut state, &mut lists, &mut culling, camera, &player_view, &transform.translation, camera_fog);
+
+        // Other screen cameras are layered over the player's in order
+        let mut overlays: Vec<_> = cameras.iter().filter(|(_, c, _)| c.target.is_none()).collect();
+        if !overlays.is_empty() {
+            overlays.sort_by_key(|(_, c, _)| c.order);
+            for (transform, overlay, overlay_fog) in overlays {
+                lists.reserve(32);
+                overlay.clear.clear(overlay.background(&clear_color, overlay_fog.unwrap_or(&scene.fog)), &config);
+                draw_view(&scene, &mut state, &mut lists, &mut culling, overlay, &view_matrix(transform), &transform.translation, overlay_fog);
+            }
+
+            // Debug draws and particles that follow are from the player's view
+            set_view(&mut culling, camera, &player_view);
+        }
 
+        // Leave the GE in the opaque state for whatever draws next
+        state.alpha_test(None);
+        state.blend(None);
+        state.depth_write(true);
     }
 }
 
-fn setup_gu() {
-    unsafe { sys::sceGuStart(GuContextType::Direct, &raw mut LIST.0 as *mut [u32; 0x40000] as *mut _) };
+fn render_debug(debug: Res<DebugDraw>, culling: Res<Culling>, mut state: ResMut<RenderState>, mut lists: ResMut<DisplayLists>) {
+    unsafe { debug.draw(&mut state, &mut lists, &culling.view_projection) };
+}
+
+fn render_particles(
+    emitters: Query<(&Transform, &ParticleEmitter)>,
+    camera: Single<&Transform, With<Player>>,
+    culling: Res<Culling>,
+    mut state: ResMut<RenderState>,
+    mut lists: ResMut<DisplayLists>
+) {
+    // Emitters are composited furthest first, like other transparent draws
+    let eye = camera.translation;
+    let distance = |t: &Transform| psp_math::distance_squared(
+        [t.translation.x, t.translation.y, t.translation.z],
+        [eye.x, eye.y, eye.z]
+    );
+    let mut sorted: Vec<_> = emitters.iter().filter(|(_, e)| !e.is_empty()).collect();
+    if sorted.is_empty() {
+        return;
+    }
+    sorted.sort_by(|(a, _), (b, _)| distance(b).total_cmp(&distance(a)));
+
+    state.alpha_test(None);
+    state.depth_write(false);
+    for (_, emitter) in sorted {
+        unsafe { emitter.draw(&mut state, &mut lists, &culling.view) };
+    }
+    state.depth_write(true);
+    state.blend(None);
 }
 
-fn finish_gu(mut asset_server: ResMut<AssetServer>) {
+/// Bloom and vignette, over the scene but under the HUD
+fn render_post(post: Res<PostProcess>, framebuffers: Res<Framebuffers>, mut state: ResMut<RenderState>, mut lists: ResMut<DisplayLists>) {
+    begin_2d(&mut state);
+    unsafe { post.draw_scene(&mut state, &mut lists, &framebuffers) };
+    end_2d(&mut state);
+}
+
+fn render_sprites(sprites: Query<&Sprite>, mut state: ResMut<RenderState>, mut lists: ResMut<DisplayLists>) {
+    if sprites.is_empty() {
+        return;
+    }
+
+    // Stable, so sprites on the same layer keep their order and can still batch
+    let mut sorted: Vec<&Sprite> = sprites.iter().collect();
+    sorted.sort_by_key(|s| s.z);
+
+    begin_2d(&mut state);
+    unsafe { draw_sprites(&mut state, &mut lists, &sorted) };
+    end_2d(&mut state);
+}
+
+fn render_text(texts: Query<&Text>, mut state: ResMut<RenderState>, mut lists: ResMut<DisplayLists>) {
+    if texts.is_empty() {
+        return;
+    }
+
+    begin_2d(&mut state);
+    for text in texts.iter() {
+        unsafe {
+            lists.reserve(text.list_words());
+            text.draw(&mut state);
+        }
+    }
+    end_2d(&mut state);
+}
+
+/// Tint and flash, over everything
+fn render_fades(post: Res<PostProcess>, mut state: ResMut<RenderState>, mut lists: ResMut<DisplayLists>) {
+    begin_2d(&mut state);
+    unsafe { post.draw_overlay(&mut state, &mut lists) };
+    end_2d(&mut state);
+}
+
+fn setup_gu(
+    mut state: ResMut<RenderState>,
+    mut lists: ResMut<DisplayLists>,
+    mut asset_server: ResMut<AssetServer>,
+    mut framebuffers: ResMut<Framebuffers>,
+    config: Res<DisplayConfig>
+) {
     unsafe {
+        let since = sys::sceKernelGetSystemTimeLow();
 
-        // Finish Gu list and wait for all gu calls to finish
-        sys::sceGuFinish();
-        sys::sceGuSync(GuSyncMode::Finish, GuSyncBehavior::Wait);
-        
-        // Draw any debug text
-        sys::sceGuDebugFlush();
-        
-        // Wait for vertical sync 
-        sys::sceDisplayWaitVblankStart();
+        // Wait for the GE to finish the previous frame, which has been drawing while the update
+        // schedule ran, then present it
+        if lists.wait() {
+            // Draw any debug text
+            sys::sceGuDebugFlush();
 
-        // Swap draw and display buffers
-        sys::sceGuSwapBuffers();
+            // Wait for vertical sync 
+            if config.vsync {
+                sys::sceDisplayWaitVblankStart();
+            }
 
-        println!("Handles: {:?}\nAssets: {}", asset_server.check_references("cell_brick.png"), asset_server.size());
-        
-        // Drop any assets that have no attached entities or stored handles
-        asset_server.drop_unused(); 
+            // Swap draw and display buffers
+            framebuffers.display = framebuffers.draw;
+            framebuffers.draw = sys::sceGuSwapBuffers();
+
+            // The GE is idle, so no texture is in use anymore.
+            // Drop any assets that have no attached entities or stored handles
+            asset_server.drop_unused(); 
+        }
+        lists.waited_since(since);
+
+        // Record this frame into the list the GE isn't using
+        lists.begin_frame();
     }
+    state.begin_frame();
+}
+
+/// Save the frame that was just presented when the screenshot combo is pressed
+fn capture_screenshot(
+    mut screenshot: ResMut<Screenshot>,
+    controller: Res<Controller>,
+    framebuffers: Res<Framebuffers>,
+    time: Res<Time>
+) {
+    screenshot.tick(time.delta_seconds());
+    if !screenshot.triggered(controller.buttons) {
+        return;
+    }
+
+    let result = unsafe { screenshot.capture(&framebuffers) };
+    screenshot.report(&result);
+}
+
+fn finish_gu(
+    asset_server: Res<AssetServer>,
+    state: Res<RenderState>,
+    mut lists: ResMut<DisplayLists>,
+    mut stats: ResMut<StatsOverlay>,
+    screenshot: Res<Screenshot>
+) {
+    // Finish Gu list and let the GE run it while the next update happens
+    unsafe { lists.kick() };
+
+    // Warn before a growing scene overruns the list
+    #[cfg(debug_assertions)]
+    if lists.near_capacity() {
+        print_at!(0, SCREEN_HEIGHT - 24, 0xff00ffff, "warning: display list {}/{} bytes, {} splits", lists.usage.peak_bytes, lists.capacity(), lists.usage.splits);
+    }
+
+    // Flag materials that still point at textures the asset server has dropped
+    #[cfg(debug_assertions)]
+    if state.stats.missing_textures > 0 {
+        print_at!(0, SCREEN_HEIGHT - 16, 0xff0000ff, "warning: {} draws use an unloaded texture", state.stats.missing_textures);
+    }
+
+    // FPS, timings and counters, toggled with SELECT + TRIANGLE
+    stats.draw(&state, &lists, &asset_server);
+    screenshot.draw();
 }
 
 fn setup_world(
@@ -392,34 +1046,129 @@ fn setup_world(
     world.spawn((
         Player, 
         Transform::default(),
+        Camera::default(),
     ));
     
-    let brick_material = Material::new(&brick_handle, TexturePixelFormat::Psm8888, true, false);
-    let font_material = Material::new(&font_handle, TexturePixelFormat::Psm8888, false, true);
+    let brick_material = Material::new(&brick_handle, TexturePixelFormat::Psm8888, true, AlphaMode::Opaque);
+    let font_material = Material::new(&font_handle, TexturePixelFormat::Psm8888, false, AlphaMode::Blend);
+    // default_font.png is a 16x16 grid of 8x8 cells covering the first 256 code points
+    let text_font = TextFont::new(&font_handle, TexturePixelFormat::Psm8888, false, FontMetrics::grid('\0', 16, 16, 8, 8));
     
     // Spawn world objects
+    world.spawn((
+        Mesh::cube_indexed(1.0),
+        Transform::from_xyz(0.0, 0.0, -2.0),
+        brick_material.clone(), // Should only clone a weak handle to the texture
+        LEVEL_GEOMETRY,
+        CastsShadow::planar().with_ground(-0.5)
+    ));
+
     world.spawn_batch(vec![
         (
-            Mesh::cube_indexed(1.0),
-            Transform::from_xyz(0.0, 0.0, -2.0),
-            brick_material.clone() // Should only clone a weak handle to the texture
-        ),
-        (
-            Mesh::cuboid(0.5, 2.0, 3.0),
+            Mesh::cuboid(0.5, 2.0, 3.0).with_layout(VertexLayout::COMPACT),
             Transform::from_xyz(3.0, 0.5, -2.0).with_rotation(0.0, PI/2.0, 0.0),
-            brick_material.clone()
+            brick_material.clone(),
+            LEVEL_GEOMETRY
         ),
         (
-            Mesh::subdivided_plane(10.0, 10.0, 2, 2),
+            Mesh::subdivided_plane(10.0, 10.0, 2, 2).with_layout(VertexLayout::COMPACT),
             Transform::from_xyz(0.0, -0.5, 0.0).with_rotation(-PI/2.0, 0.0, 0.0),
-            brick_material
+            brick_material.clone(),
+            LEVEL_GEOMETRY
         ),
-        (
-            Mesh::plane(3.0, 3.0),
-            Transform::from_xyz(-1.0, 1.0, -1.0).with_rotation(0.0, PI/2.0, 0.0),
-            font_material
-        )
     ]);
+
+    // Dense wall that drops to decimated variants as the camera backs away
+    let wall = Mesh::subdivided_plane(4.0, 2.0, 16, 8);
+    let wall_lod = Lod::generate(&wall, &[6.0, 12.0], 0.5);
+    world.spawn((
+        wall,
+        wall_lod,
+        Transform::from_xyz(0.0, 0.5, -6.0),
+        brick_material.clone(),
+    ));
+
+    // Security camera in the corner, shown on a monitor by the wall
+    let monitor_feed = RenderTarget::new(world.resource::<Vram>(), 128, 128, TexturePixelFormat::Psm8888);
+    let monitor_material = Material::new(monitor_feed.handle(), TexturePixelFormat::Psm8888, false, AlphaMode::Opaque);
+    world.spawn((
+        Camera::default().with_target(monitor_feed),
+        Transform::from_xyz(4.0, 1.5, 2.0).with_rotation(-0.3, 0.9, 0.0),
+    ));
+    world.spawn((
+        Mesh::plane(1.5, 1.5),
+        Transform::from_xyz(-3.0, 1.0, -3.0),
+        monitor_material.clone(),
+    ));
+
+    // Chrome cube reflecting the same feed
+    world.spawn((
+        Mesh::cube_indexed(0.75).with_normals(Precision::Fixed8),
+        Transform::from_xyz(-1.5, 0.0, -3.0).with_rotation(0.0, PI / 4.0, 0.0),
+        monitor_material.with_mapping(TextureMapping::SphereMap),
+    ));
+
+    // Cel shaded block with an ink outline
+    let toon = Toon::new(psp_toon::ramp(&[0xFF505050, 0xFFA0A0A0, 0xFFFFFFFF])).with_outline(0.04, 0xFF000000);
+    world.spawn((
+        Mesh::cube_indexed(0.75).with_normals(Precision::Fixed8),
+        Transform::from_xyz(1.5, 0.0, -3.0),
+        Material::toon(0xFF3080FF, toon),
+    ));
+
+    // Block that squashes and then stretches, blended by the GE between three shapes
+    let block = Mesh::cube_indexed(0.6);
+    let reshape = |scale: [f32; 3]| {
+        let mut attributes = block.attributes();
+        attributes.iter_mut().for_each(|a| (0..3).for_each(|i| a.position[i] *= scale[i]));
+        Mesh::from_attributes(block.layout, &attributes)
+    };
+    let key = |time: f32, target: usize| MorphKey { time, weights: MorphWeights::target(target).weights };
+    world.spawn((
+        block.with_morph_targets(&[&reshape([1.4, 0.5, 1.4]), &reshape([0.7, 1.8, 0.7])]),
+        Transform::from_xyz(0.0, 0.0, -4.5),
+        Material::solid(0xFF40C060),
+        MorphWeights::default().with_animation(MorphAnimation::new(
+            vec![key(0.0, 0), key(0.75, 1), key(1.5, 2), key(2.25, 0)],
+            true,
+        )),
+    ));
+
+    world.spawn(DirectionalLight::new([0.4, -1.0, -0.3], 0xFFFFFFFF));
+
+    // Small fountain next to the cube
+    let mut fountain = ParticleEmitter::new(64);
+    fountain.rate = 30.0;
+    fountain.lifetime = (1.0, 1.5);
+    fountain.speed = (2.0, 3.0);
+    fountain.spread = 0.3;
+    fountain.gravity = [0.0, -4.0, 0.0];
+    fountain.color_over_life = vec![(0.0, 0xFF40A0FF), (1.0, 0x002040FF)];
+    fountain.size_over_life = vec![(0.0, 0.15), (1.0, 0.05)];
+    fountain.blend = BlendMode::Additive;
+    world.spawn((fountain, Transform::from_xyz(-3.0, -0.5, -4.0)));
+
+    // Health bar in the top left corner, with a brick icon next to it
+    world.spawn(Sprite::solid(0xFF202020, 104.0, 12.0).with_position(28.0, 10.0));
+    world.spawn(Sprite::solid(0xFF2020E0, 100.0, 8.0).with_position(30.0, 12.0).with_z(1));
+    world.spawn(
+        Sprite::new(&brick_handle, TexturePixelFormat::Psm8888, true)
+            .with_size(16.0, 16.0)
+            .with_position(8.0, 8.0)
+    );
+
+    world.spawn(
+        Text::new("ESO", &text_font)
+            .with_position(SCREEN_WIDTH as f32 / 2.0, 8.0)
+            .with_scale(2.0)
+            .with_align(TextAlign::Center)
+    );
+
+    world.spawn((
+        Mesh::plane(3.0, 3.0),
+        Transform::from_xyz(-1.0, 1.0, -1.0).with_rotation(0.0, PI/2.0, 0.0),
+        font_material
+    ));
 }
 
 unsafe fn psp_main_inner() {
@@ -429,6 +1178,29 @@ unsafe fn psp_main_inner() {
     world.insert_resource(Time::default());
     world.insert_resource(Controller::default());
     world.insert_resource(AssetServer::default());
+    // Swap for `DisplayConfig::rgb565()` to trade color depth for VRAM
+    let display = DisplayConfig::default();
+    world.insert_resource(RenderState::for_display(&display));
+    world.insert_resource(display);
+    world.insert_resource(Vram::take());
+    world.insert_resource(Framebuffers::default());
+    world.insert_resource(DisplayLists::default());
+    world.insert_resource(StaticGroups::default());
+    world.insert_resource(Culling::default());
+    world.insert_resource(DebugDraw::default());
+    world.insert_resource(Screenshot::default());
+    world.insert_resource(StatsOverlay::default());
+    world.insert_resource(Skybox::gradient(0xFFE0C8A0, 0xFF803010));
+    world.insert_resource(ClearColor::default());
+    world.insert_resource(Fog::new(12.0, 38.0, 0xFFE0C8A0));
+
+    // Open on black and fade the level in
+    let mut post = PostProcess::default();
+    post.tint = 0xFF000000;
+    post.bloom = Some(Bloom::new(world.resource::<Vram>()));
+    post.vignette = Vignette { enabled: true, ..Default::default() };
+    post.fade_in(1.0);
+    world.insert_resource(post);
 
     // Create schedule
     let mut startup_schedule = Schedule::default();
@@ -451,6 +1223,12 @@ unsafe fn psp_main_inner() {
             update_time,
             update_controls, 
             update_player.after(update_controls),
+            update_lod.after(update_player),
+            update_morphs.after(update_time),
+            update_particles.after(update_time),
+            draw_debug_gizmos,
+            update_post.after(update_time),
+            update_stats_overlay.after(update_controls),
         )
     );
 
@@ -458,9 +1236,19 @@ unsafe fn psp_main_inner() {
     render_schedule.add_systems(
         (
             setup_gu.before(clear_screen),
+            capture_screenshot.after(setup_gu),
             clear_screen,
-            render_world.after(clear_screen),
-            finish_gu.after(render_world)
+            invalidate_static_draws.before(record_static_draws),
+            record_static_draws.after(clear_screen),
+            render_world.after(record_static_draws),
+            render_debug.after(render_world),
+            expire_debug_draw.after(render_debug),
+            render_particles.after(render_debug),
+            render_post.after(render_particles),
+            render_sprites.after(render_post),
+            render_text.after(render_sprites),
+            render_fades.after(render_text),
+            finish_gu.after(render_fades)
         )
     );
 
@@ -471,7 +1259,9 @@ unsafe fn psp_main_inner() {
     // Main game loop
     loop {
         // This updates game logic 
+        let update_start = sys::sceKernelGetSystemTimeLow();
         update_schedule.run(&mut world);
+        world.resource_mut::<StatsOverlay>().update_us = sys::sceKernelGetSystemTimeLow().wrapping_sub(update_start);
 
         render_schedule.run(&mut world);
     }
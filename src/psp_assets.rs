@@ -1,10 +1,10 @@
 use core::{alloc::Layout, ffi::c_void};
 
 use aligned_vec::{AVec, ConstAlign};
-use alloc::{alloc::dealloc, boxed::Box, ffi::CString, fmt, format, slice, string::{String, ToString}, sync::Arc, vec::Vec};
+use alloc::{alloc::dealloc, boxed::Box, ffi::CString, fmt, format, slice, string::{String, ToString}, sync::Arc, vec, vec::Vec};
 use hashbrown::HashMap;
 use bevy_ecs::resource::Resource;
-use psp::sys::{sceIoClose, sceIoGetstat, sceIoOpen, sceIoOpenAsync, sceIoRead, sceIoReadAsync, IoOpenFlags, SceIoStat, SceUid};
+use psp::sys::{sceIoClose, sceIoGetstat, sceIoMkdir, sceIoOpen, sceIoOpenAsync, sceIoRead, sceIoReadAsync, sceIoWrite, IoOpenFlags, SceIoStat, SceUid};
 
 use crate::psp_image::load_png_swizzled;
 use crate::psp_image::load_png;
@@ -15,7 +15,15 @@ pub struct TextureHandle {
     width: usize,
     height: usize,
     pitch: usize,
-    pixels: AVec<u8, ConstAlign<16>>,
+    pixels: Pixels,
+}
+
+/// Where a texture's pixels live
+#[derive(Clone, Debug)]
+enum Pixels {
+    Heap(AVec<u8, ConstAlign<16>>),
+    /// Uncached VRAM address, e.g. a render target the GE draws into
+    Vram(usize),
 }
 
 pub struct File {
@@ -51,16 +59,96 @@ pub fn open_file(filepath: String, io_flags: IoOpenFlags) -> Result<File, IoErro
     }
 }
 
+/// Read a whole file into memory
+pub fn read_file(filepath: &'_ str) -> Result<Vec<u8>, IoError> {
+    unsafe {
+        let fd = open_file(String::from(filepath), IoOpenFlags::RD_ONLY)?;
+
+        let mut data = vec![0u8; fd.size as usize];
+        let read = sceIoRead(fd.fd, data.as_mut_ptr() as *mut c_void, fd.size as u32);
+        sceIoClose(fd.fd);
+        if read < 0 {
+            return Err(IoError(format!("Could not read file \"{}\" of size: {}", filepath, fd.size)));
+        }
+
+        Ok(data)
+    }
+}
+
+/// Write `data` to a file, replacing anything already there
+pub fn write_file(filepath: &'_ str, data: &[u8]) -> Result<(), IoError> {
+    unsafe {
+        let path = CString::new(filepath).map_err(|_| IoError(format!("Invalid path: {}", filepath)))?;
+
+        let fd = sceIoOpen(path.as_ptr() as *const u8, IoOpenFlags::WR_ONLY | IoOpenFlags::CREAT | IoOpenFlags::TRUNC, 0o777);
+        if fd.0 < 0 {
+            return Err(IoError(format!("Failed to create file: {}", filepath)));
+        }
+
+        let written = sceIoWrite(fd, data.as_ptr() as *const c_void, data.len());
+        sceIoClose(fd);
+        if written < 0 || written as usize != data.len() {
+            return Err(IoError(format!("Could not write {} bytes to \"{}\"", data.len(), filepath)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether anything exists at `filepath`
+pub fn file_exists(filepath: &'_ str) -> bool {
+    let Ok(path) = CString::new(filepath) else {
+        return false;
+    };
+    let mut stat: SceIoStat = unsafe { core::mem::zeroed() };
+    unsafe { sceIoGetstat(path.as_ptr() as *const u8, &mut stat) >= 0 }
+}
+
+/// Create a directory, succeeding if it is already there
+pub fn create_dir(dirpath: &'_ str) -> Result<(), IoError> {
+    if file_exists(dirpath) {
+        return Ok(());
+    }
+
+    let path = CString::new(dirpath).map_err(|_| IoError(format!("Invalid path: {}", dirpath)))?;
+    if unsafe { sceIoMkdir(path.as_ptr() as *const u8, 0o777) } < 0 {
+        return Err(IoError(format!("Could not create directory: {}", dirpath)));
+    }
+    Ok(())
+}
+
 impl TextureHandle {
     pub fn new(width: usize, height: usize, pitch: usize, pixels: AVec<u8, ConstAlign<16>>) -> Self {
         TextureHandle {
             width,
             height,
             pitch,
-            pixels,
+            pixels: Pixels::Heap(pixels),
+        }
+    }
+
+    /// Texture backed by VRAM the caller owns, which has to outlive the handle
+    pub fn from_vram(width: usize, height: usize, pitch: usize, address: *mut u8) -> Self {
+        TextureHandle {
+            width,
+            height,
+            pitch,
+            pixels: Pixels::Vram(address as usize),
+        }
+    }
+
+    /// Bytes of main memory holding the pixels; zero for textures in VRAM
+    pub fn heap_bytes(&self) -> usize {
+        match &self.pixels {
+            Pixels::Heap(pixels) => pixels.len(),
+            Pixels::Vram(_) => 0,
         }
     }
 
+    pub fn is_vram(&self) -> bool {
+        matches!(self.pixels, Pixels::Vram(_))
+    }
+
     pub fn width(&self) -> usize {
         self.width
     }
@@ -74,7 +162,10 @@ impl TextureHandle {
     }
 
     pub fn raw_bytes(&self) -> *const c_void {
-        self.pixels.as_ptr() as *const c_void
+        match &self.pixels {
+            Pixels::Heap(pixels) => pixels.as_ptr() as *const c_void,
+            Pixels::Vram(address) => *address as *const c_void,
+        }
     }
 }
 
@@ -224,7 +315,7 @@ impl Default for AssetServer {
 }
 
 #[derive(Debug, Clone)]
-pub struct IoError(String);
+pub struct IoError(pub(crate) String);
 
 impl fmt::Display for IoError {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -261,6 +352,11 @@ impl AssetServer {
         self.texture_map.len()
     }
 
+    /// Bytes of main memory used by loaded textures
+    pub fn memory(&self) -> usize {
+        self.texture_map.values().map(|entry| entry.handle.heap_bytes()).sum()
+    }
+
     /// Get a strong handle to the texture
     pub fn get(&self, key: &'_ str) -> Option<Arc<TextureHandle>> {
         self.texture_map.get(key).map(|entry| entry.handle.clone())
@@ -0,0 +1,287 @@
+use core::ffi::c_void;
+use core::f32::consts::PI;
+use core::mem::size_of;
+
+use alloc::{string::String, vec::Vec};
+use bevy_ecs::resource::Resource;
+use psp::sys::{self, CtrlButtons, GuPrimitive, GuState, ScePspFMatrix4, VertexType};
+use psp::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use crate::print_at;
+use crate::psp_assets::AssetServer;
+use crate::psp_math;
+use crate::psp_render::{DisplayLists, RenderState};
+
+/// Segments used for each circle of a wire sphere
+const CIRCLE_SEGMENTS: usize = 16;
+
+/// Lines drawn per call, so a single batch never needs more than a slice of the display list
+const LINES_PER_BATCH: usize = 512;
+
+#[derive(Clone, Copy)]
+struct DebugLine {
+    start: [f32; 3],
+    end: [f32; 3],
+    color: u32,
+    /// Seconds left to live; anything at or below zero goes at the next `tick`
+    remaining: f32,
+}
+
+struct DebugLabel {
+    position: [f32; 3],
+    text: String,
+    color: u32,
+    remaining: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LineVertex {
+    color: u32,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+/// Immediate mode shapes for debugging. Anything pushed during the update schedule is drawn that
+/// frame and then dropped, unless it was given a duration with one of the `*_for` variants.
+/// Colors are ABGR.
+#[derive(Resource)]
+pub struct DebugDraw {
+    /// Pushes are ignored while disabled, so gizmo code can stay in place in release builds
+    pub enabled: bool,
+    lines: Vec<DebugLine>,
+    labels: Vec<DebugLabel>,
+    /// Duration applied to shapes pushed without one
+    duration: f32,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        DebugDraw {
+            enabled: cfg!(debug_assertions),
+            lines: Vec::new(),
+            labels: Vec::new(),
+            duration: 0.0,
+        }
+    }
+}
+
+impl DebugDraw {
+    /// Age everything by `dt` seconds and drop what has expired
+    pub fn tick(&mut self, dt: f32) {
+        self.lines.retain_mut(|l| {
+            l.remaining -= dt;
+            l.remaining > 0.0
+        });
+        self.labels.retain_mut(|l| {
+            l.remaining -= dt;
+            l.remaining > 0.0
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.labels.clear();
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Run `f` with every shape it pushes kept alive for `seconds`
+    pub fn for_duration(&mut self, seconds: f32, f: impl FnOnce(&mut Self)) {
+        let previous = core::mem::replace(&mut self.duration, seconds);
+        f(self);
+        self.duration = previous;
+    }
+
+    pub fn line(&mut self, start: [f32; 3], end: [f32; 3], color: u32) {
+        if self.enabled {
+            self.lines.push(DebugLine { start, end, color, remaining: self.duration });
+        }
+    }
+
+    pub fn line_for(&mut self, start: [f32; 3], end: [f32; 3], color: u32, seconds: f32) {
+        self.for_duration(seconds, |d| d.line(start, end, color));
+    }
+
+    /// Box from its eight corners, in the order returned by `Aabb::corners`
+    pub fn box_corners(&mut self, c: &[[f32; 3]; 8], color: u32) {
+        const EDGES: [(usize, usize); 12] = [
+            (0, 1), (2, 3), (4, 5), (6, 7),
+            (0, 2), (1, 3), (4, 6), (5, 7),
+            (0, 4), (1, 5), (2, 6), (3, 7),
+        ];
+        for (a, b) in EDGES {
+            self.line(c[a], c[b], color);
+        }
+    }
+
+    /// Axis aligned box
+    pub fn aabb(&mut self, min: [f32; 3], max: [f32; 3], color: u32) {
+        let corner = |i: usize| [
+            if i & 1 == 0 { min[0] } else { max[0] },
+            if i & 2 == 0 { min[1] } else { max[1] },
+            if i & 4 == 0 { min[2] } else { max[2] },
+        ];
+        self.box_corners(&core::array::from_fn(corner), color);
+    }
+
+    /// Three circles, one around each axis
+    pub fn wire_sphere(&mut self, center: [f32; 3], radius: f32, color: u32) {
+        let points: [(f32, f32); CIRCLE_SEGMENTS] = core::array::from_fn(|i| {
+            let angle = i as f32 * 2.0 * PI / CIRCLE_SEGMENTS as f32;
+            (psp_math::vfpu_cosf(angle) * radius, psp_math::vfpu_sinf(angle) * radius)
+        });
+
+        let [x, y, z] = center;
+        for i in 0..CIRCLE_SEGMENTS {
+            let (a0, b0) = points[i];
+            let (a1, b1) = points[(i + 1) % CIRCLE_SEGMENTS];
+            self.line([x + a0, y + b0, z], [x + a1, y + b1, z], color);
+            self.line([x + a0, y, z + b0], [x + a1, y, z + b1], color);
+            self.line([x, y + a0, z + b0], [x, y + a1, z + b1], color);
+        }
+    }
+
+    /// X, Y and Z in red, green and blue
+    pub fn axes(&mut self, origin: [f32; 3], size: f32) {
+        let [x, y, z] = origin;
+        self.line(origin, [x + size, y, z], 0xFF0000FF);
+        self.line(origin, [x, y + size, z], 0xFF00FF00);
+        self.line(origin, [x, y, z + size], 0xFFFF0000);
+    }
+
+    /// Square grid on the XZ plane, `size` wide with `divisions` cells per side
+    pub fn grid(&mut self, center: [f32; 3], size: f32, divisions: u32, color: u32) {
+        let half = size * 0.5;
+        let step = size / divisions.max(1) as f32;
+        let [x, y, z] = center;
+        for i in 0..=divisions {
+            let offset = -half + i as f32 * step;
+            self.line([x + offset, y, z - half], [x + offset, y, z + half], color);
+            self.line([x - half, y, z + offset], [x + half, y, z + offset], color);
+        }
+    }
+
+    /// Text anchored to a point in the world, drawn with the debug font
+    pub fn text(&mut self, position: [f32; 3], text: impl Into<String>, color: u32) {
+        if self.enabled {
+            self.labels.push(DebugLabel { position, text: text.into(), color, remaining: self.duration });
+        }
+    }
+
+    /// Draw everything queued with depth testing against the scene. Needs the model matrix mode
+    /// active, and `view_projection` to match the camera the scene was drawn with.
+    pub unsafe fn draw(&self, state: &mut RenderState, lists: &mut DisplayLists, view_projection: &ScePspFMatrix4) {
+        if !self.lines.is_empty() {
+            sys::sceGumLoadIdentity();
+            state.set(GuState::Texture2D, false);
+            state.alpha_test(None);
+            state.blend(None);
+            state.depth_write(true);
+
+            for batch in self.lines.chunks(LINES_PER_BATCH) {
+                let bytes = batch.len() * 2 * size_of::<LineVertex>();
+                lists.reserve(bytes.div_ceil(4) + 32);
+
+                let vertices = sys::sceGuGetMemory(bytes as i32) as *mut LineVertex;
+                for (i, line) in batch.iter().enumerate() {
+                    let vertex = |p: [f32; 3]| LineVertex { color: line.color, x: p[0], y: p[1], z: p[2] };
+                    vertices.add(i * 2).write(vertex(line.start));
+                    vertices.add(i * 2 + 1).write(vertex(line.end));
+                }
+
+                let vtype = VertexType::COLOR_8888 | VertexType::VERTEX_32BITF | VertexType::TRANSFORM_3D;
+                state.draw(GuPrimitive::Lines, vtype, batch.len() * 2, core::ptr::null(), vertices as *const c_void);
+            }
+        }
+
+        for label in &self.labels {
+            let [x, y, _, w] = psp_math::transform_point(view_projection, label.position);
+            if w <= 0.0 {
+                continue;
+            }
+
+            let sx = (x / w * 0.5 + 0.5) * SCREEN_WIDTH as f32;
+            let sy = (0.5 - y / w * 0.5) * SCREEN_HEIGHT as f32;
+            if sx < 0.0 || sy < 0.0 || sx >= SCREEN_WIDTH as f32 || sy >= SCREEN_HEIGHT as f32 {
+                continue;
+            }
+
+            // Debug text is composited by the CPU when the frame is flushed, not through the list
+            print_at!(sx, sy, label.color, "{}", label.text);
+        }
+    }
+}
+
+/// Frame statistics printed over the top of the screen with the debug font
+#[derive(Resource)]
+pub struct StatsOverlay {
+    pub visible: bool,
+    /// Pressing all of these together shows or hides the overlay
+    pub toggle: CtrlButtons,
+    /// Time the last update schedule took, filled in by the main loop
+    pub update_us: u32,
+    /// Smoothed so the number can be read
+    fps: f32,
+    held: bool,
+}
+
+impl Default for StatsOverlay {
+    fn default() -> Self {
+        StatsOverlay {
+            visible: false,
+            toggle: CtrlButtons::SELECT | CtrlButtons::TRIANGLE,
+            update_us: 0,
+            fps: 0.0,
+            held: false,
+        }
+    }
+}
+
+impl StatsOverlay {
+    /// Flip `visible` when the toggle combo is first pressed
+    pub fn handle_input(&mut self, buttons: CtrlButtons) {
+        let held = buttons.contains(self.toggle);
+        if held && !self.held {
+            self.visible = !self.visible;
+        }
+        self.held = held;
+    }
+
+    /// Print the last frame's numbers. Call after the frame's lists are kicked, so the timings
+    /// and counters are final.
+    pub fn draw(&mut self, state: &RenderState, lists: &DisplayLists, assets: &AssetServer) {
+        let timing = lists.timing;
+        if timing.frame_us > 0 {
+            let fps = 1.0e6 / timing.frame_us as f32;
+            self.fps = if self.fps > 0.0 { self.fps * 0.9 + fps * 0.1 } else { fps };
+        }
+        if !self.visible {
+            return;
+        }
+
+        let ms = |us: u32| us as f32 * 1.0e-3;
+        let stats = &state.stats;
+        let color: u32 = 0xff00ff00;
+
+        print_at!(0, 0, color, "FPS {:.1}  frame {:.1}ms", self.fps, ms(timing.frame_us));
+        print_at!(
+            0, 8, color, "update {:.1}ms  render {:.1}ms  GE {:.1}ms  wait {:.1}ms",
+            ms(self.update_us), ms(timing.cpu_us.saturating_sub(self.update_us)), ms(timing.gpu_us), ms(timing.wait_us)
+        );
+        print_at!(
+            0, 16, color, "draws {} (lists {})  tris {}  binds {}",
+            stats.draw_calls, stats.call_lists, stats.triangles, stats.texture_binds
+        );
+        print_at!(0, 24, color, "state {} sent / {} skipped", stats.state_changes, stats.state_skips);
+        print_at!(
+            0, 32, color, "list {}B  peak {}/{}B  splits {}",
+            lists.usage.bytes, lists.usage.peak_bytes, lists.capacity(), lists.usage.splits
+        );
+        print_at!(0, 40, color, "visible {}  culled {}", stats.visible, stats.culled);
+        print_at!(0, 48, color, "assets {}  {}KB", assets.size(), assets.memory() / 1024);
+    }
+}
@@ -1,10 +1,12 @@
 use aligned_vec::{AVec, ConstAlign, avec};
-use alloc::{boxed::Box, string::String, sync::{Arc, Weak}};
+use alloc::{boxed::Box, string::String, sync::{Arc, Weak}, vec::Vec};
 use bevy_ecs::component::Component;
-use psp::sys::{GuPrimitive, TexturePixelFormat};
+use hashbrown::HashMap;
+use psp::sys::{GuPrimitive, TexturePixelFormat, VertexType};
 
-use crate::{psp_assets::TextureHandle, psp_image::load_png_swizzled};
+use crate::{psp_assets::TextureHandle, psp_image::load_png_swizzled, psp_math, psp_toon::Toon};
 
+/// Default vertex, laid out as [`VertexLayout::DEFAULT`] (float UVs followed by a float position).
 #[repr(C, align(4))]
 #[derive(Clone, Copy)]
 pub struct Vertex {
@@ -15,14 +17,436 @@ pub struct Vertex {
     pub z: f32,
 }
 
+/// Storage precision of a vertex component.
+///
+/// Fixed point values are normalized by the GE: 8-bit values are divided by 128 and 16-bit values
+/// by 32768. Signed components (normals, positions) therefore cover [-1, 1) and unsigned ones
+/// (UVs, weights) cover [0, 2).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Precision {
+    Fixed8,
+    Fixed16,
+    Float,
+}
+
+impl Precision {
+    /// Size in bytes of a single element
+    pub const fn size(self) -> usize {
+        match self {
+            Precision::Fixed8 => 1,
+            Precision::Fixed16 => 2,
+            Precision::Float => 4,
+        }
+    }
+}
+
+/// Packed vertex color formats supported by the GE. Channels are stored red in the low bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VertexColor {
+    Rgb5650,
+    Rgba5551,
+    Rgba4444,
+    Rgba8888,
+}
+
+impl VertexColor {
+    /// Size in bytes of a single color
+    pub const fn size(self) -> usize {
+        match self {
+            VertexColor::Rgba8888 => 4,
+            _ => 2,
+        }
+    }
+}
+
+/// Describes which components a mesh's vertices carry and how each one is stored.
+///
+/// Components are written in the order the GE expects them: weights, texture, color, normal,
+/// position. Each component is aligned to its own element size and the whole vertex is padded to
+/// the largest one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    /// Precision and number (1-8) of skinning weights
+    pub weights: Option<(Precision, u8)>,
+    pub texture: Option<Precision>,
+    pub color: Option<VertexColor>,
+    pub normal: Option<Precision>,
+    pub position: Precision,
+}
+
+/// Byte offsets of every component inside a single vertex.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VertexOffsets {
+    pub weights: usize,
+    pub texture: usize,
+    pub color: usize,
+    pub normal: usize,
+    pub position: usize,
+    pub stride: usize,
+}
+
+impl Default for VertexLayout {
+    fn default() -> Self {
+        VertexLayout::DEFAULT
+    }
+}
+
+impl VertexLayout {
+    /// Layout matching [`Vertex`]
+    pub const DEFAULT: VertexLayout = VertexLayout::new(Precision::Float).with_texture(Precision::Float);
+
+    /// 16-bit UVs and positions; half the size of [`VertexLayout::DEFAULT`]. Suited to static geometry
+    pub const COMPACT: VertexLayout = VertexLayout::new(Precision::Fixed16).with_texture(Precision::Fixed16);
+
+    /// A layout carrying only a position
+    pub const fn new(position: Precision) -> Self {
+        VertexLayout {
+            weights: None,
+            texture: None,
+            color: None,
+            normal: None,
+            position,
+        }
+    }
+
+    pub const fn with_weights(mut self, precision: Precision, count: u8) -> Self {
+        self.weights = Some((precision, count));
+        self
+    }
+
+    pub const fn with_texture(mut self, precision: Precision) -> Self {
+        self.texture = Some(precision);
+        self
+    }
+
+    pub const fn with_color(mut self, color: VertexColor) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub const fn with_normal(mut self, precision: Precision) -> Self {
+        self.normal = Some(precision);
+        self
+    }
+
+    /// The `VertexType` bits describing this layout. Index and transform bits are left unset.
+    pub fn vertex_type(&self) -> VertexType {
+        let mut vt = match self.position {
+            Precision::Fixed8 => VertexType::VERTEX_8BIT,
+            Precision::Fixed16 => VertexType::VERTEX_16BIT,
+            Precision::Float => VertexType::VERTEX_32BITF,
+        };
+
+        vt |= match self.texture {
+            Some(Precision::Fixed8) => VertexType::TEXTURE_8BIT,
+            Some(Precision::Fixed16) => VertexType::TEXTURE_16BIT,
+            Some(Precision::Float) => VertexType::TEXTURE_32BITF,
+            None => VertexType::empty(),
+        };
+
+        vt |= match self.color {
+            Some(VertexColor::Rgb5650) => VertexType::COLOR_5650,
+            Some(VertexColor::Rgba5551) => VertexType::COLOR_5551,
+            Some(VertexColor::Rgba4444) => VertexType::COLOR_4444,
+            Some(VertexColor::Rgba8888) => VertexType::COLOR_8888,
+            None => VertexType::empty(),
+        };
+
+        vt |= match self.normal {
+            Some(Precision::Fixed8) => VertexType::NORMAL_8BIT,
+            Some(Precision::Fixed16) => VertexType::NORMAL_16BIT,
+            Some(Precision::Float) => VertexType::NORMAL_32BITF,
+            None => VertexType::empty(),
+        };
+
+        if let Some((precision, count)) = self.weights {
+            vt |= match precision {
+                Precision::Fixed8 => VertexType::WEIGHT_8BIT,
+                Precision::Fixed16 => VertexType::WEIGHT_16BIT,
+                Precision::Float => VertexType::WEIGHT_32BITF,
+            };
+            vt |= match count {
+                0 | 1 => VertexType::WEIGHTS1,
+                2 => VertexType::WEIGHTS2,
+                3 => VertexType::WEIGHTS3,
+                4 => VertexType::WEIGHTS4,
+                5 => VertexType::WEIGHTS5,
+                6 => VertexType::WEIGHTS6,
+                7 => VertexType::WEIGHTS7,
+                _ => VertexType::WEIGHTS8,
+            };
+        }
+
+        vt
+    }
+
+    /// Compute where each component lives inside a vertex and the total stride
+    pub const fn offsets(&self) -> VertexOffsets {
+        const fn align(offset: usize, to: usize) -> usize {
+            (offset + to - 1) & !(to - 1)
+        }
+
+        let mut o = VertexOffsets { weights: 0, texture: 0, color: 0, normal: 0, position: 0, stride: 0 };
+        let mut cursor = 0;
+        let mut largest = 1;
+
+        if let Some((p, count)) = self.weights {
+            o.weights = align(cursor, p.size());
+            cursor = o.weights + p.size() * count as usize;
+            if p.size() > largest { largest = p.size() }
+        }
+        if let Some(p) = self.texture {
+            o.texture = align(cursor, p.size());
+            cursor = o.texture + p.size() * 2;
+            if p.size() > largest { largest = p.size() }
+        }
+        if let Some(c) = self.color {
+            o.color = align(cursor, c.size());
+            cursor = o.color + c.size();
+            if c.size() > largest { largest = c.size() }
+        }
+        if let Some(p) = self.normal {
+            o.normal = align(cursor, p.size());
+            cursor = o.normal + p.size() * 3;
+            if p.size() > largest { largest = p.size() }
+        }
+
+        let p = self.position;
+        o.position = align(cursor, p.size());
+        cursor = o.position + p.size() * 3;
+        if p.size() > largest { largest = p.size() }
+
+        o.stride = align(cursor, largest);
+        o
+    }
+
+    /// Size of a single vertex in bytes
+    pub const fn stride(&self) -> usize {
+        self.offsets().stride
+    }
+
+    /// Write `attr` into `out` (which must be at least [`Self::stride`] bytes long).
+    /// Compressed positions are divided by `scale` before being quantized.
+    pub fn encode(&self, attr: &VertexAttributes, scale: f32, out: &mut [u8]) {
+        let o = self.offsets();
+
+        if let Some((p, count)) = self.weights {
+            for i in 0..count.min(8) as usize {
+                write_unsigned(p, attr.weights[i], &mut out[o.weights + i * p.size()..]);
+            }
+        }
+        if let Some(p) = self.texture {
+            write_unsigned(p, attr.uv[0], &mut out[o.texture..]);
+            write_unsigned(p, attr.uv[1], &mut out[o.texture + p.size()..]);
+        }
+        if let Some(c) = self.color {
+            let packed = pack_color(c, attr.color);
+            match c {
+                VertexColor::Rgba8888 => out[o.color..o.color + 4].copy_from_slice(&packed.to_le_bytes()),
+                _ => out[o.color..o.color + 2].copy_from_slice(&(packed as u16).to_le_bytes()),
+            }
+        }
+        if let Some(p) = self.normal {
+            for i in 0..3 {
+                write_signed(p, attr.normal[i], &mut out[o.normal + i * p.size()..]);
+            }
+        }
+
+        let p = self.position;
+        let inv = if p == Precision::Float || scale == 0.0 { 1.0 } else { 1.0 / scale };
+        for i in 0..3 {
+            write_signed(p, attr.position[i] * inv, &mut out[o.position + i * p.size()..]);
+        }
+    }
+
+    /// Read a vertex written by [`Self::encode`] back into full precision
+    pub fn decode(&self, bytes: &[u8], scale: f32) -> VertexAttributes {
+        let o = self.offsets();
+        let mut attr = VertexAttributes::default();
+
+        if let Some((p, count)) = self.weights {
+            for i in 0..count.min(8) as usize {
+                attr.weights[i] = read_unsigned(p, &bytes[o.weights + i * p.size()..]);
+            }
+        }
+        if let Some(p) = self.texture {
+            attr.uv[0] = read_unsigned(p, &bytes[o.texture..]);
+            attr.uv[1] = read_unsigned(p, &bytes[o.texture + p.size()..]);
+        }
+        if let Some(c) = self.color {
+            let packed = match c {
+                VertexColor::Rgba8888 => u32::from_le_bytes([bytes[o.color], bytes[o.color + 1], bytes[o.color + 2], bytes[o.color + 3]]),
+                _ => u16::from_le_bytes([bytes[o.color], bytes[o.color + 1]]) as u32,
+            };
+            attr.color = unpack_color(c, packed);
+        }
+        if let Some(p) = self.normal {
+            for i in 0..3 {
+                attr.normal[i] = read_signed(p, &bytes[o.normal + i * p.size()..]);
+            }
+        }
+
+        let p = self.position;
+        let s = if p == Precision::Float { 1.0 } else { scale };
+        for i in 0..3 {
+            attr.position[i] = read_signed(p, &bytes[o.position + i * p.size()..]) * s;
+        }
+
+        attr
+    }
+}
+
+/// Full precision description of a vertex, used to build and convert meshes between layouts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VertexAttributes {
+    pub position: [f32; 3],
+    pub uv: [f32; 2],
+    pub normal: [f32; 3],
+    /// ABGR8888, the same format `sceGuColor` takes
+    pub color: u32,
+    pub weights: [f32; 8],
+}
+
+impl Default for VertexAttributes {
+    fn default() -> Self {
+        VertexAttributes {
+            position: [0.0; 3],
+            uv: [0.0; 2],
+            normal: [0.0, 1.0, 0.0],
+            color: 0xffffffff,
+            weights: [0.0; 8],
+        }
+    }
+}
+
+impl From<&Vertex> for VertexAttributes {
+    fn from(vert: &Vertex) -> Self {
+        VertexAttributes {
+            position: [vert.x, vert.y, vert.z],
+            uv: [vert.u, vert.v],
+            ..Default::default()
+        }
+    }
+}
+
+#[inline]
+fn round(v: f32) -> i32 {
+    if v < 0.0 { (v - 0.5) as i32 } else { (v + 0.5) as i32 }
+}
+
+/// Unsigned normalized components (UVs, weights): 1.0 == 128 / 32768
+fn write_unsigned(p: Precision, value: f32, out: &mut [u8]) {
+    match p {
+        Precision::Fixed8 => out[0] = round(value * 128.0).clamp(0, u8::MAX as i32) as u8,
+        Precision::Fixed16 => out[..2].copy_from_slice(&(round(value * 32768.0).clamp(0, u16::MAX as i32) as u16).to_le_bytes()),
+        Precision::Float => out[..4].copy_from_slice(&value.to_le_bytes()),
+    }
+}
+
+fn read_unsigned(p: Precision, bytes: &[u8]) -> f32 {
+    match p {
+        Precision::Fixed8 => bytes[0] as f32 / 128.0,
+        Precision::Fixed16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+        Precision::Float => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    }
+}
+
+/// Signed normalized components (normals, positions): 1.0 == 128 / 32768, clamped to [-1, 1)
+fn write_signed(p: Precision, value: f32, out: &mut [u8]) {
+    match p {
+        Precision::Fixed8 => out[0] = round(value * 128.0).clamp(i8::MIN as i32, i8::MAX as i32) as i8 as u8,
+        Precision::Fixed16 => out[..2].copy_from_slice(&(round(value * 32768.0).clamp(i16::MIN as i32, i16::MAX as i32) as i16).to_le_bytes()),
+        Precision::Float => out[..4].copy_from_slice(&value.to_le_bytes()),
+    }
+}
+
+fn read_signed(p: Precision, bytes: &[u8]) -> f32 {
+    match p {
+        Precision::Fixed8 => bytes[0] as i8 as f32 / 128.0,
+        Precision::Fixed16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+        Precision::Float => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    }
+}
+
+/// Convert an ABGR8888 color into the packed vertex color format
+fn pack_color(format: VertexColor, abgr: u32) -> u32 {
+    let r = abgr & 0xff;
+    let g = (abgr >> 8) & 0xff;
+    let b = (abgr >> 16) & 0xff;
+    let a = (abgr >> 24) & 0xff;
+
+    match format {
+        VertexColor::Rgb5650 => (r >> 3) | ((g >> 2) << 5) | ((b >> 3) << 11),
+        VertexColor::Rgba5551 => (r >> 3) | ((g >> 3) << 5) | ((b >> 3) << 10) | ((a >> 7) << 15),
+        VertexColor::Rgba4444 => (r >> 4) | ((g >> 4) << 4) | ((b >> 4) << 8) | ((a >> 4) << 12),
+        VertexColor::Rgba8888 => abgr,
+    }
+}
+
+/// Expand a packed vertex color back to ABGR8888
+fn unpack_color(format: VertexColor, packed: u32) -> u32 {
+    // Replicate the high bits into the low bits so that full intensity stays at 0xff
+    let expand = |v: u32, bits: u32| (v << (8 - bits)) | (v >> (2 * bits - 8));
+
+    let (r, g, b, a) = match format {
+        VertexColor::Rgb5650 => (
+            expand(packed & 0x1f, 5),
+            expand((packed >> 5) & 0x3f, 6),
+            expand((packed >> 11) & 0x1f, 5),
+            0xff,
+        ),
+        VertexColor::Rgba5551 => (
+            expand(packed & 0x1f, 5),
+            expand((packed >> 5) & 0x1f, 5),
+            expand((packed >> 10) & 0x1f, 5),
+            if packed & 0x8000 != 0 { 0xff } else { 0 },
+        ),
+        VertexColor::Rgba4444 => (
+            expand(packed & 0xf, 4),
+            expand((packed >> 4) & 0xf, 4),
+            expand((packed >> 8) & 0xf, 4),
+            expand((packed >> 12) & 0xf, 4),
+        ),
+        VertexColor::Rgba8888 => return packed,
+    };
+
+    r | (g << 8) | (b << 16) | (a << 24)
+}
+
+/// How a material treats the alpha channel of its texture
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Alpha is ignored
+    Opaque,
+    /// Cutout: fragments with alpha at or below the cutoff are discarded, the rest are opaque
+    Mask(u8),
+    /// Alpha blended. Drawn after all opaque geometry, back to front, without writing depth
+    Blend,
+}
+
+/// Where a material's texture coordinates come from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextureMapping {
+    /// The mesh's own UVs
+    #[default]
+    Uv,
+    /// Generated from the mesh's normals as seen from the camera, so the texture is read as a
+    /// sphere map and appears reflected. Needs normals; the mesh's UVs are ignored.
+    SphereMap,
+}
+
 #[repr(C, align(4))]
 #[derive(Clone, Component)]
 pub struct Material {
     pub handle: Option<Weak<TextureHandle>>,
     pub texture_format: TexturePixelFormat,
     pub swizzle: bool,
-    pub blend: bool,
-
+    pub alpha_mode: AlphaMode,
+    /// ABGR8888 color used by untextured materials, or when the texture has been unloaded
+    pub color: u32,
+    pub mapping: TextureMapping,
+    /// Cel shade instead of texturing
+    pub toon: Option<Toon>,
 }
 
 impl Default for Material {
@@ -31,36 +455,151 @@ impl Default for Material {
             handle: None,
             texture_format: TexturePixelFormat::PsmT4,
             swizzle: false,
-            blend: false,
+            alpha_mode: AlphaMode::Opaque,
+            color: 0xffffffff,
+            mapping: TextureMapping::Uv,
+            toon: None,
         }
     }
 }
 
 impl Material {
-    pub fn new(handle: &Arc<TextureHandle>, texture_format: TexturePixelFormat, swizzle: bool, blend: bool) -> Self {
+    pub fn new(handle: &Arc<TextureHandle>, texture_format: TexturePixelFormat, swizzle: bool, alpha_mode: AlphaMode) -> Self {
         Material {
             handle: Some(Arc::downgrade(handle)),
             texture_format,
             swizzle,
-            blend
+            alpha_mode,
+            ..Default::default()
+        }
+    }
+
+    /// An untextured material drawn in a single ABGR8888 color
+    pub fn solid(color: u32) -> Self {
+        let alpha_mode = if color >> 24 == 0xff { AlphaMode::Opaque } else { AlphaMode::Blend };
+        Material {
+            color,
+            alpha_mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_mapping(mut self, mapping: TextureMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    /// A cel shaded material in a single ABGR8888 color
+    pub fn toon(color: u32, toon: Toon) -> Self {
+        Material {
+            toon: Some(toon),
+            ..Material::solid(color)
+        }
+    }
+
+    /// Whether this material belongs in the transparent pass
+    pub fn is_transparent(&self) -> bool {
+        self.alpha_mode == AlphaMode::Blend
+    }
+
+    /// Key used to order draws so that entities sharing GE state end up next to each other.
+    /// Opaque materials come first, then cutouts, then blended ones; ties are grouped by texture.
+    pub fn sort_key(&self) -> (u8, usize, bool) {
+        let mode = match self.alpha_mode {
+            AlphaMode::Opaque => 0,
+            AlphaMode::Mask(_) => 1,
+            AlphaMode::Blend => 2,
+        };
+        let texture = self.handle.as_ref().map_or(0, |h| Weak::as_ptr(h) as usize);
+        (mode, texture, self.swizzle)
+    }
+}
+
+/// Axis aligned bounding box in mesh space
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    pub fn from_points(points: impl Iterator<Item = [f32; 3]>) -> Self {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        let mut any = false;
+
+        for p in points {
+            any = true;
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
         }
+
+        if any { Aabb { min, max } } else { Aabb::default() }
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        [
+            (self.min[0] + self.max[0]) * 0.5,
+            (self.min[1] + self.max[1]) * 0.5,
+            (self.min[2] + self.max[2]) * 0.5,
+        ]
+    }
+
+    /// The eight corners, in the order `sceGuBeginObject` expects a bounding box
+    pub fn corners(&self) -> [[f32; 3]; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            [a[0], a[1], a[2]], [b[0], a[1], a[2]], [b[0], b[1], a[2]], [a[0], b[1], a[2]],
+            [a[0], a[1], b[2]], [b[0], a[1], b[2]], [b[0], b[1], b[2]], [a[0], b[1], b[2]],
+        ]
     }
 }
 
+/// Bounding sphere in mesh space
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BoundingSphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
 #[repr(C, align(4))]
 #[derive(Component)]
 pub struct Mesh {
-    pub vertices: AVec<Vertex, ConstAlign<16>>,
+    /// Raw vertex data, `layout.stride()` bytes per vertex and morph target
+    pub vertices: AVec<u8, ConstAlign<16>>,
+    pub layout: VertexLayout,
+    pub vertex_count: usize,
+    /// Versions of every vertex stored back to back, blended by the GE with [`MorphWeights`].
+    /// 1 for a mesh that doesn't morph.
+    pub morph_targets: usize,
+    /// Factor compressed (8/16-bit) positions were divided by; applied as a model scale when drawn
+    pub scale: f32,
     pub indices: Option<AVec<u16, ConstAlign<16>>>,
     pub primitive_type: GuPrimitive,
+    /// Cached bounds, see [`Mesh::update_bounds`]
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+    /// `aabb` corners as float vertices for GE-side bounding box tests, divided by `scale` like the
+    /// positions they bound. Kept on the heap since the GE reads it after the frame has been
+    /// handed off.
+    pub bounding_box: AVec<[f32; 3], ConstAlign<16>>,
 }
 
 impl Default for Mesh {
     fn default() -> Self {
         Mesh {
             vertices: AVec::new(16),
+            layout: VertexLayout::DEFAULT,
+            vertex_count: 0,
+            morph_targets: 1,
+            scale: 1.0,
             indices: None,
-            primitive_type: GuPrimitive::Triangles
+            primitive_type: GuPrimitive::Triangles,
+            aabb: Aabb::default(),
+            sphere: BoundingSphere::default(),
+            bounding_box: AVec::new(16),
         }
     }
 }
@@ -71,13 +610,304 @@ const fn v(x: f32, y: f32, z: f32, u: f32, v: f32) -> Vertex {
 }
 
 impl Mesh {
+    /// Build a mesh in the default layout from a slice of [`Vertex`]
+    pub fn from_vertices(vertices: &[Vertex]) -> Mesh {
+        // Vertex is repr(C) and matches VertexLayout::DEFAULT byte for byte
+        let bytes = unsafe {
+            core::slice::from_raw_parts(vertices.as_ptr() as *const u8, vertices.len() * size_of::<Vertex>())
+        };
+
+        let mut mesh = Mesh {
+            vertices: AVec::from_slice(16, bytes),
+            vertex_count: vertices.len(),
+            ..Default::default()
+        };
+        mesh.update_bounds();
+        mesh
+    }
+
+    /// Build a mesh with an arbitrary layout. If the layout stores positions as 8 or 16-bit values,
+    /// they are normalized against the largest coordinate and [`Mesh::scale`] is set to match.
+    pub fn from_attributes(layout: VertexLayout, attributes: &[VertexAttributes]) -> Mesh {
+        Mesh::from_morph_targets(layout, &[attributes])
+    }
+
+    /// Build a mesh that morphs between `targets`, which all need the same number of vertices.
+    /// The GE blends at most [`MAX_MORPH_TARGETS`]; any more are dropped.
+    pub fn from_morph_targets(layout: VertexLayout, targets: &[&[VertexAttributes]]) -> Mesh {
+        let targets = &targets[..targets.len().min(MAX_MORPH_TARGETS)];
+        let vertex_count = targets.first().map_or(0, |t| t.len());
+        assert!(targets.iter().all(|t| t.len() == vertex_count), "morph targets need the same vertex count");
+
+        // Every target shares the scale, so it has to fit the largest of them. The largest
+        // positive fixed point value is just under 1, which the scale makes up for.
+        let max = targets
+            .iter()
+            .flat_map(|t| t.iter())
+            .flat_map(|a| a.position)
+            .fold(0.0f32, |m, p| m.max(p.abs()));
+        let scale = match layout.position {
+            Precision::Float => 1.0,
+            _ if max <= 0.0 => 1.0,
+            Precision::Fixed8 => max * 128.0 / 127.0,
+            Precision::Fixed16 => max * 32768.0 / 32767.0,
+        };
+
+        let stride = layout.stride();
+        let size = stride * targets.len() * vertex_count;
+        let mut vertices = AVec::<u8, ConstAlign<16>>::with_capacity(16, size);
+        vertices.resize(size, 0);
+        // The GE reads a vertex's targets one after the other
+        for (i, out) in vertices.chunks_exact_mut(stride).enumerate() {
+            layout.encode(&targets[i % targets.len()][i / targets.len()], scale, out);
+        }
+
+        let mut mesh = Mesh {
+            vertices,
+            layout,
+            vertex_count,
+            morph_targets: targets.len().max(1),
+            scale,
+            ..Default::default()
+        };
+        mesh.update_bounds();
+        mesh
+    }
+
+    /// Copy of this mesh that morphs from its shape to each of `targets`, which need the
+    /// same vertices in the same order. Meshes that already morph contribute their first target.
+    pub fn with_morph_targets(&self, targets: &[&Mesh]) -> Mesh {
+        let attributes: Vec<_> = core::iter::once(self.attributes())
+            .chain(targets.iter().map(|t| t.attributes()))
+            .collect();
+        let slices: Vec<&[VertexAttributes]> = attributes.iter().map(|a| a.as_slice()).collect();
+
+        Mesh {
+            indices: self.indices.clone(),
+            primitive_type: self.primitive_type,
+            ..Mesh::from_morph_targets(self.layout, &slices)
+        }
+    }
+
+    /// Recompute the cached bounding volumes. Needed after editing `vertices` directly.
+    pub fn update_bounds(&mut self) {
+        // Bounds cover every target, so whatever blend is drawn stays inside them
+        let attributes = self.all_attributes();
+        let positions = || attributes.iter().flatten().map(|a| a.position);
+
+        self.aabb = Aabb::from_points(positions());
+
+        let center = self.aabb.center();
+        let radius_sq = positions().fold(0.0f32, |r, p| r.max(psp_math::distance_squared(p, center)));
+        self.sphere = BoundingSphere { center, radius: psp_math::vfpu_sqrtf(radius_sq) };
+
+        // The box is drawn after the model matrix scales compressed positions back up
+        let corners = self.aabb.corners().map(|c| c.map(|v| v / self.scale));
+        self.bounding_box = AVec::from_slice(16, &corners);
+    }
+
+    /// Decode every vertex back into full precision. Only the first morph target is decoded.
+    pub fn attributes(&self) -> Vec<VertexAttributes> {
+        self.target_attributes(0)
+    }
+
+    /// Decode every vertex of one morph target
+    pub fn target_attributes(&self, target: usize) -> Vec<VertexAttributes> {
+        let stride = self.layout.stride();
+        self.vertices
+            .chunks_exact(stride * self.morph_targets)
+            .take(self.vertex_count)
+            .map(|bytes| self.layout.decode(&bytes[stride * target..stride * (target + 1)], self.scale))
+            .collect()
+    }
+
+    fn all_attributes(&self) -> Vec<Vec<VertexAttributes>> {
+        (0..self.morph_targets).map(|t| self.target_attributes(t)).collect()
+    }
+
+    /// Re-encode this mesh and its morph targets into another layout, keeping its indices and
+    /// primitive type. Components missing from the source layout are filled with
+    /// [`VertexAttributes::default`].
+    pub fn with_layout(&self, layout: VertexLayout) -> Mesh {
+        let targets = self.all_attributes();
+        let slices: Vec<&[VertexAttributes]> = targets.iter().map(|a| a.as_slice()).collect();
+
+        Mesh {
+            indices: self.indices.clone(),
+            primitive_type: self.primitive_type,
+            ..Mesh::from_morph_targets(layout, &slices)
+        }
+    }
+
+    /// Copy of this mesh with normals stored at `precision`, averaged from the faces around each
+    /// vertex. Vertices that aren't shared, like the cube's, end up with flat normals. Front faces
+    /// are wound clockwise, matching what the GE culls. Each morph target gets its own normals.
+    pub fn with_normals(&self, precision: Precision) -> Mesh {
+        let triangles = self.triangles();
+        let mut targets = self.all_attributes();
+
+        for attributes in targets.iter_mut() {
+            attributes.iter_mut().for_each(|a| a.normal = [0.0; 3]);
+
+            for &[a, b, c] in &triangles {
+                let (a, b, c) = (a as usize, b as usize, c as usize);
+                let p = |i: usize| attributes[i].position;
+                let ab = [0, 1, 2].map(|i| p(b)[i] - p(a)[i]);
+                let ac = [0, 1, 2].map(|i| p(c)[i] - p(a)[i]);
+                // Left unnormalized so bigger faces count for more
+                let face = psp_math::cross(ac, ab);
+                for v in [a, b, c] {
+                    (0..3).for_each(|i| attributes[v].normal[i] += face[i]);
+                }
+            }
+            attributes.iter_mut().for_each(|a| a.normal = psp_math::normalize(a.normal));
+        }
+
+        let slices: Vec<&[VertexAttributes]> = targets.iter().map(|a| a.as_slice()).collect();
+        Mesh {
+            indices: self.indices.clone(),
+            primitive_type: self.primitive_type,
+            ..Mesh::from_morph_targets(self.layout.with_normal(precision), &slices)
+        }
+    }
+
+    /// Every triangle of the mesh as three vertex indices, whatever its primitive type.
+    /// Point, line and sprite meshes have no triangles.
+    pub fn triangles(&self) -> Vec<[u16; 3]> {
+        let index = |i: usize| match &self.indices {
+            Some(indices) => indices[i],
+            None => i as u16,
+        };
+        let count = self.draw_count();
+
+        match self.primitive_type {
+            GuPrimitive::Triangles => (0..count / 3)
+                .map(|t| [index(t * 3), index(t * 3 + 1), index(t * 3 + 2)])
+                .collect(),
+            // Every other triangle of a strip is wound the other way round
+            GuPrimitive::TriangleStrip => (2..count)
+                .map(|i| if i % 2 == 0 {
+                    [index(i - 2), index(i - 1), index(i)]
+                } else {
+                    [index(i - 1), index(i - 2), index(i)]
+                })
+                .collect(),
+            GuPrimitive::TriangleFan => (2..count)
+                .map(|i| [index(0), index(i - 1), index(i)])
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Build a lower detail copy of this mesh by vertex clustering: the bounding box is split into
+    /// a grid `resolution` cells across its longest side, every vertex in a cell is merged into one
+    /// and triangles that collapse are dropped. UVs are averaged per cell, so texture seams smear;
+    /// this is meant for meshes seen from a distance. Only the first morph target is kept.
+    pub fn decimate(&self, resolution: u32) -> Mesh {
+        let attributes = self.attributes();
+
+        let extent = (0..3)
+            .map(|i| self.aabb.max[i] - self.aabb.min[i])
+            .fold(0.0f32, f32::max);
+        let cell = if extent > 0.0 { extent / resolution.max(1) as f32 } else { 1.0 };
+
+        // Map each cell to the vertex replacing it, accumulating the vertices that fall into it
+        let mut cells = HashMap::<[i32; 3], u16>::new();
+        let mut merged: Vec<(VertexAttributes, u32)> = Vec::new();
+        let remap: Vec<u16> = attributes
+            .iter()
+            .map(|a| {
+                let key = [0, 1, 2].map(|i| ((a.position[i] - self.aabb.min[i]) / cell) as i32);
+                let id = *cells.entry(key).or_insert_with(|| {
+                    merged.push((VertexAttributes { position: [0.0; 3], uv: [0.0; 2], ..*a }, 0));
+                    (merged.len() - 1) as u16
+                });
+
+                let (sum, n) = &mut merged[id as usize];
+                (0..3).for_each(|i| sum.position[i] += a.position[i]);
+                (0..2).for_each(|i| sum.uv[i] += a.uv[i]);
+                *n += 1;
+                id
+            })
+            .collect();
+
+        let vertices: Vec<VertexAttributes> = merged
+            .into_iter()
+            .map(|(mut a, n)| {
+                let inv = 1.0 / n as f32;
+                a.position.iter_mut().for_each(|p| *p *= inv);
+                a.uv.iter_mut().for_each(|u| *u *= inv);
+                a
+            })
+            .collect();
+
+        let mut indices = AVec::<u16, ConstAlign<16>>::new(16);
+        for [a, b, c] in self.triangles() {
+            let (a, b, c) = (remap[a as usize], remap[b as usize], remap[c as usize]);
+            if a != b && b != c && a != c {
+                indices.extend_from_slice(&[a, b, c]);
+            }
+        }
+
+        Mesh {
+            indices: Some(indices),
+            primitive_type: GuPrimitive::Triangles,
+            ..Mesh::from_attributes(self.layout, &vertices)
+        }
+    }
+
+    /// Grid cells across the longest side of the bounds at the mesh's average edge length, i.e. the
+    /// [`Mesh::decimate`] resolution at which it would barely change
+    pub fn grid_resolution(&self) -> u32 {
+        let attributes = self.attributes();
+        let (mut total, mut edges) = (0.0f32, 0u32);
+        for [a, b, c] in self.triangles() {
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                let d = psp_math::distance_squared(attributes[from as usize].position, attributes[to as usize].position);
+                total += psp_math::vfpu_sqrtf(d);
+                edges += 1;
+            }
+        }
+
+        let extent = (0..3)
+            .map(|i| self.aabb.max[i] - self.aabb.min[i])
+            .fold(0.0f32, f32::max);
+        if edges == 0 || total <= 0.0 {
+            return 1;
+        }
+        (extent * edges as f32 / total) as u32
+    }
+
+    /// Number of vertices a draw call submits: the index count for indexed meshes
+    pub fn draw_count(&self) -> usize {
+        self.indices.as_ref().map_or(self.vertex_count, |i| i.len())
+    }
+
+    /// Vertex type bits for drawing this mesh, including the index format and morph count
+    pub fn vertex_type(&self) -> VertexType {
+        let mut vt = self.layout.vertex_type();
+        if self.indices.is_some() {
+            vt |= VertexType::INDEX_16BIT;
+        }
+        vt |= match self.morph_targets {
+            0 | 1 => VertexType::empty(),
+            2 => VertexType::VERTICES2,
+            3 => VertexType::VERTICES3,
+            4 => VertexType::VERTICES4,
+            5 => VertexType::VERTICES5,
+            6 => VertexType::VERTICES6,
+            7 => VertexType::VERTICES7,
+            _ => VertexType::VERTICES8,
+        };
+        vt
+    }
+
     /// 36-vertex (12-triangle) unit cube, centred at the origin.
     /// NON-INDEXED. self.indexed will == None after this call, resulting in more VRAM usage
     pub fn cube(size: f32) -> Mesh {
         let h = size * 0.5; // half-extent
 
-        Mesh {
-            vertices: avec!(
+        Mesh::from_vertices(&avec!(
                 [16] |
                 // +Z face
                 v(-h,-h, h, 0.0, 0.0),
@@ -121,9 +951,7 @@ impl Mesh {
                 v(-h, -h, -h, 1.0, 0.0),
                 v(h, -h, h, 0.0, 1.0),
                 v(h, -h, -h, 1.0, 1.0)
-            ),
-            ..Default::default()
-        }
+            ))
     }
 
     pub fn cube_indexed(size: f32) -> Mesh {
@@ -173,9 +1001,8 @@ impl Mesh {
         }
 
         Mesh {
-            vertices: verts,
             indices: Some(indices),
-            ..Default::default()
+            ..Mesh::from_vertices(&verts)
         }
     }
 
@@ -225,9 +1052,9 @@ impl Mesh {
         ];
 
         Mesh {
-            vertices: verts,
             primitive_type: GuPrimitive::TriangleStrip,
             indices: Some(inds),   // u16 indices on PSP
+            ..Mesh::from_vertices(&verts)
         }
     }
 
@@ -237,8 +1064,7 @@ impl Mesh {
         let y = y_len * 0.5; // half-extent
         let z = z_len * 0.5; // half-extent
 
-        Mesh {
-            vertices: avec!(
+        Mesh::from_vertices(&avec!(
                 [16] |
                 // +Z face
                 v(-x,-y, z, 0.0, 0.0),
@@ -282,9 +1108,7 @@ impl Mesh {
                 v(-x, -y, -z, 1.0, 0.0),
                 v(x, -y, z, 0.0, 1.0),
                 v(x, -y, -z, 1.0, 1.0)
-            ),
-            ..Default::default()
-        }
+            ))
     }
 
     // Calculates a plane for the psp gu, centered at the origin
@@ -292,17 +1116,14 @@ impl Mesh {
         let x = x_len * 0.5;
         let y = y_len * 0.5;
 
-        Mesh {
-            vertices: avec!(
+        Mesh::from_vertices(&avec!(
                 [16] | v(-x, -y, 0.0, 0.0, 1.0),
                 v(-x, y, 0.0, 0.0, 0.0),
                 v(x, y, 0.0, 1.0, 0.0),
                 v(-x, -y, 0.0, 0.0, 1.0),
                 v(x, y, 0.0, 1.0, 0.0),
                 v(x, -y, 0.0, 1.0, 1.0)
-            ),
-            ..Default::default()
-        }
+            ))
     }
 
     /// A plane centered at the origin, subdivided into `subdivs_x` × `subdivs_y` quads.
@@ -316,7 +1137,7 @@ impl Mesh {
         let dy = y_len / subdivs_y as f32;
 
         // We'll generate 6 vertices per quad
-        let mut verts = AVec::new(16);
+        let mut verts = AVec::<Vertex, ConstAlign<16>>::new(16);
 
         // helper to construct your Vertex (assuming you have a const fn v)
         // const fn v(x: f32, y: f32, z: f32, u: f32, v: f32) -> Vertex { … }
@@ -352,9 +1173,179 @@ impl Mesh {
         }
 
         // now turn it into your Mesh – everything else is identical
-        Mesh {
-            vertices: verts,    // or whatever your Mesh expects
-            ..Default::default()
+        Mesh::from_vertices(&verts)
+    }
+}
+
+/// A lower detail mesh and the camera distance from which it is used
+pub struct LodLevel {
+    pub distance: f32,
+    pub mesh: Mesh,
+}
+
+/// Level of detail variants for an entity. The entity's own `Mesh` is the most detailed level and
+/// `levels` must be sorted by increasing distance.
+#[derive(Component)]
+pub struct Lod {
+    pub levels: Vec<LodLevel>,
+    /// Distance past a switch point the camera has to move before the level changes back, so
+    /// entities sitting on a boundary don't flicker between levels
+    pub hysteresis: f32,
+    /// 0 for the entity's `Mesh`, `i` for `levels[i - 1]`
+    current: usize,
+}
+
+impl Lod {
+    pub fn new(levels: Vec<LodLevel>, hysteresis: f32) -> Self {
+        Lod { levels, hysteresis, current: 0 }
+    }
+
+    /// Generate one decimated level per distance, each at half the grid resolution of the last.
+    /// The first level halves the resolution of the base mesh's own vertex spacing.
+    pub fn generate(base: &Mesh, distances: &[f32], hysteresis: f32) -> Self {
+        let resolution = base.grid_resolution();
+        let levels = distances
+            .iter()
+            .enumerate()
+            .map(|(i, &distance)| LodLevel {
+                distance,
+                mesh: base.decimate((resolution >> (i + 1)).max(2)),
+            })
+            .collect();
+
+        Lod::new(levels, hysteresis)
+    }
+
+    /// The level that should be used at `distance`, given the one currently in use
+    pub fn select(&self, distance: f32) -> usize {
+        let mut level = self.current;
+        while level < self.levels.len() && distance > self.levels[level].distance + self.hysteresis {
+            level += 1;
+        }
+        while level > 0 && distance < self.levels[level - 1].distance - self.hysteresis {
+            level -= 1;
+        }
+        level
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn set_current(&mut self, level: usize) {
+        self.current = level.min(self.levels.len());
+    }
+
+    /// The mesh to draw, given the entity's own `base` mesh
+    pub fn mesh<'a>(&'a self, base: &'a Mesh) -> &'a Mesh {
+        self.level_mesh(self.current, base)
+    }
+
+    /// The mesh of any `level`, as numbered by [`Lod::select`]
+    pub fn level_mesh<'a>(&'a self, level: usize, base: &'a Mesh) -> &'a Mesh {
+        match level.min(self.levels.len()) {
+            0 => base,
+            i => &self.levels[i - 1].mesh,
+        }
+    }
+}
+
+/// Most vertex sets the GE can blend in one draw
+pub const MAX_MORPH_TARGETS: usize = 8;
+
+/// Weights of a mesh's morph targets at a point in time
+#[derive(Clone, Copy, Debug)]
+pub struct MorphKey {
+    /// Seconds from the start of the animation
+    pub time: f32,
+    pub weights: [f32; MAX_MORPH_TARGETS],
+}
+
+/// Keyframed weights, blended linearly between keys. Keys must be sorted by time.
+#[derive(Clone, Debug)]
+pub struct MorphAnimation {
+    pub keys: Vec<MorphKey>,
+    pub looping: bool,
+    elapsed: f32,
+}
+
+impl MorphAnimation {
+    pub fn new(keys: Vec<MorphKey>, looping: bool) -> Self {
+        MorphAnimation { keys, looping, elapsed: 0.0 }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |k| k.time)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.elapsed >= self.duration()
+    }
+
+    /// Weights at the current time
+    pub fn sample(&self) -> Option<[f32; MAX_MORPH_TARGETS]> {
+        let next = self.keys.iter().position(|k| k.time > self.elapsed);
+        match next {
+            None => self.keys.last().map(|k| k.weights),
+            Some(0) => Some(self.keys[0].weights),
+            Some(i) => {
+                let (a, b) = (&self.keys[i - 1], &self.keys[i]);
+                let t = (self.elapsed - a.time) / (b.time - a.time);
+                Some(core::array::from_fn(|w| a.weights[w] + (b.weights[w] - a.weights[w]) * t))
+            }
+        }
+    }
+}
+
+/// How much each of a mesh's morph targets contributes to what is drawn. The GE takes the sum of
+/// every target times its weight, so weights usually add up to 1. Without this component a
+/// morphing mesh draws as its first target.
+#[derive(Component, Clone, Debug)]
+pub struct MorphWeights {
+    pub weights: [f32; MAX_MORPH_TARGETS],
+    /// Drives `weights` while it plays
+    pub animation: Option<MorphAnimation>,
+}
+
+impl Default for MorphWeights {
+    fn default() -> Self {
+        let mut weights = [0.0; MAX_MORPH_TARGETS];
+        weights[0] = 1.0;
+        MorphWeights { weights, animation: None }
+    }
+}
+
+impl MorphWeights {
+    /// Show only `target`
+    pub fn target(target: usize) -> Self {
+        let mut weights = [0.0; MAX_MORPH_TARGETS];
+        weights[target.min(MAX_MORPH_TARGETS - 1)] = 1.0;
+        MorphWeights { weights, animation: None }
+    }
+
+    pub fn with_animation(mut self, animation: MorphAnimation) -> Self {
+        self.animation = Some(animation);
+        self
+    }
+
+    /// Advance the animation by `dt` seconds. Returns whether the weights changed.
+    pub fn tick(&mut self, dt: f32) -> bool {
+        let Some(animation) = self.animation.as_mut().filter(|a| !a.is_finished()) else {
+            return false;
+        };
+
+        animation.elapsed += dt;
+        let duration = animation.duration();
+        if animation.looping && duration > 0.0 {
+            animation.elapsed %= duration;
+        }
+
+        match animation.sample() {
+            Some(weights) => {
+                self.weights = weights;
+                true
+            }
+            None => false,
         }
     }
 }
@@ -0,0 +1,26 @@
+use bevy_ecs::component::Component;
+
+use crate::psp_math;
+
+/// Light shining uniformly from one direction, like the sun. Where only one is used, the first
+/// one found is treated as the primary light.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct DirectionalLight {
+    /// Unit vector pointing the way the light travels, i.e. away from the light
+    pub direction: [f32; 3],
+    /// ABGR
+    pub color: u32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: [f32; 3], color: u32) -> Self {
+        DirectionalLight { direction: psp_math::normalize(direction), color }
+    }
+}
+
+impl Default for DirectionalLight {
+    /// White light from straight above
+    fn default() -> Self {
+        DirectionalLight { direction: [0.0, -1.0, 0.0], color: 0xFFFFFFFF }
+    }
+}
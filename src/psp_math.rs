@@ -1,4 +1,4 @@
-use psp::{self, sys::{sceKernelUtilsMt19937Init, sceKernelUtilsMt19937UInt, sceRtcGetCurrentTick, SceKernelUtilsMt19937Context}};
+use psp::{self, sys::{sceKernelUtilsMt19937Init, sceKernelUtilsMt19937UInt, sceRtcGetCurrentTick, SceKernelUtilsMt19937Context, ScePspFMatrix4, ScePspFVector3, ScePspFVector4}};
 
 pub fn rand() -> u32 {
     unsafe {
@@ -11,6 +11,41 @@ pub fn rand() -> u32 {
     }
 }
 
+/// Cheap xorshift generator for when many numbers are needed per frame. `rand` reseeds from the
+/// clock on every call, so it repeats itself when called in quick succession.
+#[derive(Clone, Copy, Debug)]
+pub struct Rng(u32);
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        Rng(seed.max(1))
+    }
+
+    /// Seeded from the RTC
+    pub fn from_clock() -> Self {
+        Rng::new(rand())
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Uniform in `[min, max)`
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
 /// Calculate the cosine of an angle using the psp VFPU
 pub fn vfpu_cosf(x: f32) -> f32 {
     let mut ret_val = 0.0;
@@ -55,3 +90,160 @@ pub fn vfpu_sinf(x: f32) -> f32 {
     ret_val
 }
 
+/// Calculate the square root of a value using the psp VFPU
+pub fn vfpu_sqrtf(x: f32) -> f32 {
+    let mut ret_val = 0.0;
+
+    unsafe {
+
+        psp::vfpu_asm!(
+            "mtv    {x}, S000",
+            "vsqrt.s S000, S000",
+            "mfv    {ret}, S000",
+
+            x = inout(reg) x => _,
+            ret = out(reg) ret_val,
+            options(nostack, nomem),
+        );
+    }
+
+    ret_val
+}
+
+#[inline]
+pub fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[inline]
+pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// `v` scaled to unit length, or unchanged if it has none
+pub fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = vfpu_sqrtf(dot(v, v));
+    if len > 0.0 { [v[0] / len, v[1] / len, v[2] / len] } else { v }
+}
+
+/// Linear blend between two ABGR colors
+pub fn lerp_color(a: u32, b: u32, t: f32) -> u32 {
+    (0..4).fold(0, |out, i| {
+        let shift = i * 8;
+        let ca = ((a >> shift) & 0xFF) as f32;
+        let cb = ((b >> shift) & 0xFF) as f32;
+        out | (((ca + (cb - ca) * t) as u32) << shift)
+    })
+}
+
+/// Squared distance between two points
+#[inline]
+pub fn distance_squared(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    dot(d, d)
+}
+
+/// Rotate `v` the same way `sceGumRotateXYZ(r)` rotates the model matrix
+pub fn rotate_xyz(v: [f32; 3], r: &ScePspFVector3) -> [f32; 3] {
+    // The matrix ends up as Rx * Ry * Rz, so Z is applied to the vector first
+    let (sz, cz) = (vfpu_sinf(r.z), vfpu_cosf(r.z));
+    let v = [v[0] * cz - v[1] * sz, v[0] * sz + v[1] * cz, v[2]];
+
+    let (sy, cy) = (vfpu_sinf(r.y), vfpu_cosf(r.y));
+    let v = [v[0] * cy + v[2] * sy, v[1], -v[0] * sy + v[2] * cy];
+
+    let (sx, cx) = (vfpu_sinf(r.x), vfpu_cosf(r.x));
+    [v[0], v[1] * cx - v[2] * sx, v[1] * sx + v[2] * cx]
+}
+
+pub fn mat4_identity() -> ScePspFMatrix4 {
+    let col = |x, y, z, w| ScePspFVector4 { x, y, z, w };
+    ScePspFMatrix4 {
+        x: col(1.0, 0.0, 0.0, 0.0),
+        y: col(0.0, 1.0, 0.0, 0.0),
+        z: col(0.0, 0.0, 1.0, 0.0),
+        w: col(0.0, 0.0, 0.0, 1.0),
+    }
+}
+
+/// Transform a point (`w = 1`) by a column-major matrix, returning homogeneous coordinates
+pub fn transform_point(m: &ScePspFMatrix4, p: [f32; 3]) -> [f32; 4] {
+    [
+        m.x.x * p[0] + m.y.x * p[1] + m.z.x * p[2] + m.w.x,
+        m.x.y * p[0] + m.y.y * p[1] + m.z.y * p[2] + m.w.y,
+        m.x.z * p[0] + m.y.z * p[1] + m.z.z * p[2] + m.w.z,
+        m.x.w * p[0] + m.y.w * p[1] + m.z.w * p[2] + m.w.w,
+    ]
+}
+
+/// Multiply two column-major matrices, `a * b`
+pub fn mat4_mul(a: &ScePspFMatrix4, b: &ScePspFMatrix4) -> ScePspFMatrix4 {
+    let col = |c: &ScePspFVector4| ScePspFVector4 {
+        x: a.x.x * c.x + a.y.x * c.y + a.z.x * c.z + a.w.x * c.w,
+        y: a.x.y * c.x + a.y.y * c.y + a.z.y * c.z + a.w.y * c.w,
+        z: a.x.z * c.x + a.y.z * c.y + a.z.z * c.z + a.w.z * c.w,
+        w: a.x.w * c.x + a.y.w * c.y + a.z.w * c.z + a.w.w * c.w,
+    };
+
+    ScePspFMatrix4 { x: col(&b.x), y: col(&b.y), z: col(&b.z), w: col(&b.w) }
+}
+
+/// The six clipping planes of a camera, each stored as `(normal, distance)` with the normal
+/// pointing into the volume.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [[f32; 4]; 6],
+}
+
+impl Default for Frustum {
+    /// A frustum that contains everything
+    fn default() -> Self {
+        Frustum { planes: [[0.0, 0.0, 0.0, 1.0]; 6] }
+    }
+}
+
+impl Frustum {
+    /// Extract the planes of a combined `projection * view` matrix
+    pub fn from_matrix(m: &ScePspFMatrix4) -> Self {
+        let row = |i: usize| {
+            let c = |v: &ScePspFVector4| [v.x, v.y, v.z, v.w][i];
+            [c(&m.x), c(&m.y), c(&m.z), c(&m.w)]
+        };
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+
+        let add = |a: [f32; 4], b: [f32; 4]| [a[0] + b[0], a[1] + b[1], a[2] + b[2], a[3] + b[3]];
+        let sub = |a: [f32; 4], b: [f32; 4]| [a[0] - b[0], a[1] - b[1], a[2] - b[2], a[3] - b[3]];
+
+        let mut planes = [add(r3, r0), sub(r3, r0), add(r3, r1), sub(r3, r1), add(r3, r2), sub(r3, r2)];
+
+        // Normalize so distances come out in world units
+        for p in planes.iter_mut() {
+            let len = vfpu_sqrtf(dot([p[0], p[1], p[2]], [p[0], p[1], p[2]]));
+            if len > 0.0 {
+                p.iter_mut().for_each(|v| *v /= len);
+            }
+        }
+
+        Frustum { planes }
+    }
+
+    /// Whether any part of a sphere is inside the frustum
+    pub fn intersects_sphere(&self, center: [f32; 3], radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|p| dot([p[0], p[1], p[2]], center) + p[3] >= -radius)
+    }
+
+    /// Whether any part of an axis aligned box is inside the frustum
+    pub fn intersects_aabb(&self, min: [f32; 3], max: [f32; 3]) -> bool {
+        self.planes.iter().all(|p| {
+            // Test the corner furthest along the plane normal
+            let corner = [
+                if p[0] >= 0.0 { max[0] } else { min[0] },
+                if p[1] >= 0.0 { max[1] } else { min[1] },
+                if p[2] >= 0.0 { max[2] } else { min[2] },
+            ];
+            dot([p[0], p[1], p[2]], corner) + p[3] >= 0.0
+        })
+    }
+}
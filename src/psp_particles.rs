@@ -0,0 +1,286 @@
+use core::ffi::c_void;
+use core::f32::consts::PI;
+use core::mem::size_of;
+
+use alloc::{sync::{Arc, Weak}, vec, vec::Vec};
+use bevy_ecs::component::Component;
+use psp::sys::{self, GuPrimitive, GuState, ScePspFMatrix4, TextureColorComponent, TextureEffect, TexturePixelFormat, VertexType};
+
+use crate::psp_assets::TextureHandle;
+use crate::psp_math::{self, Rng};
+use crate::psp_render::{BlendMode, DisplayLists, RenderState};
+
+/// A number of particles released at once, `time` seconds into each emitter cycle
+#[derive(Clone, Copy, Debug)]
+pub struct Burst {
+    pub time: f32,
+    pub count: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    position: [f32; 3],
+    velocity: [f32; 3],
+    age: f32,
+    lifetime: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ParticleVertex {
+    u: f32,
+    v: f32,
+    color: u32,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+/// Spawns and simulates particles in world space around its entity's `Transform`. The pool is
+/// allocated once with `capacity` slots; once it is full new particles are dropped.
+#[derive(Component)]
+pub struct ParticleEmitter {
+    /// Particles per second
+    pub rate: f32,
+    pub bursts: Vec<Burst>,
+    /// Length of one emitter cycle; bursts repeat every cycle. `None` plays them once.
+    pub cycle: Option<f32>,
+    /// Seconds each particle lives, picked between the two
+    pub lifetime: (f32, f32),
+    /// Launch direction, half angle of the cone around it in radians, and speed range
+    pub direction: [f32; 3],
+    pub spread: f32,
+    pub speed: (f32, f32),
+    /// Acceleration applied every frame
+    pub gravity: [f32; 3],
+    /// `(t, ABGR)` keys over normalized lifetime, interpolated linearly
+    pub color_over_life: Vec<(f32, u32)>,
+    /// `(t, world size)` keys over normalized lifetime
+    pub size_over_life: Vec<(f32, f32)>,
+    pub texture: Option<Weak<TextureHandle>>,
+    pub texture_format: TexturePixelFormat,
+    pub swizzle: bool,
+    /// Atlas layout as `(columns, rows)`; particles step through the frames over their lifetime
+    pub frames: (u16, u16),
+    pub blend: BlendMode,
+    /// Stops continuous emission without killing live particles
+    pub emitting: bool,
+    particles: Vec<Particle>,
+    capacity: usize,
+    /// Fractional particles owed by `rate` since the last spawn
+    accumulator: f32,
+    age: f32,
+    /// Particles requested with `burst` since the last update
+    pending: u32,
+    rng: Rng,
+}
+
+impl ParticleEmitter {
+    pub fn new(capacity: usize) -> Self {
+        ParticleEmitter {
+            rate: 10.0,
+            bursts: Vec::new(),
+            cycle: None,
+            lifetime: (1.0, 1.0),
+            direction: [0.0, 1.0, 0.0],
+            spread: PI / 8.0,
+            speed: (1.0, 1.0),
+            gravity: [0.0, 0.0, 0.0],
+            color_over_life: vec![(0.0, 0xFFFFFFFF), (1.0, 0x00FFFFFF)],
+            size_over_life: vec![(0.0, 0.25)],
+            texture: None,
+            texture_format: TexturePixelFormat::Psm8888,
+            swizzle: false,
+            frames: (1, 1),
+            blend: BlendMode::Alpha,
+            emitting: true,
+            particles: Vec::with_capacity(capacity),
+            capacity,
+            accumulator: 0.0,
+            age: 0.0,
+            pending: 0,
+            rng: Rng::from_clock(),
+        }
+    }
+
+    pub fn with_texture(mut self, handle: &Arc<TextureHandle>, format: TexturePixelFormat, swizzle: bool) -> Self {
+        self.texture = Some(Arc::downgrade(handle));
+        self.texture_format = format;
+        self.swizzle = swizzle;
+        self
+    }
+
+    pub fn with_frames(mut self, columns: u16, rows: u16) -> Self {
+        self.frames = (columns.max(1), rows.max(1));
+        self
+    }
+
+    /// Release `count` particles on the next update
+    pub fn burst(&mut self, count: u32) {
+        self.pending += count;
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Age, move and spawn particles. `origin` is the emitter's world position.
+    pub fn update(&mut self, origin: [f32; 3], dt: f32) {
+        let gravity = self.gravity;
+        self.particles.retain_mut(|p| {
+            p.age += dt;
+            for i in 0..3 {
+                p.velocity[i] += gravity[i] * dt;
+                p.position[i] += p.velocity[i] * dt;
+            }
+            p.age < p.lifetime
+        });
+
+        // Bursts whose time falls inside this frame, wrapping into the next cycle if needed
+        let previous = self.age;
+        self.age += dt;
+        let mut count = core::mem::take(&mut self.pending);
+        for burst in &self.bursts {
+            if burst.time >= previous && burst.time < self.age {
+                count += burst.count;
+            }
+        }
+        if let Some(cycle) = self.cycle.filter(|c| *c > 0.0) {
+            if self.age >= cycle {
+                self.age -= cycle;
+                count += self.bursts.iter().filter(|b| b.time < self.age).map(|b| b.count).sum::<u32>();
+            }
+        }
+
+        if self.emitting {
+            self.accumulator += self.rate * dt;
+            let whole = self.accumulator as u32;
+            count += whole;
+            self.accumulator -= whole as f32;
+        }
+
+        for _ in 0..count {
+            if self.particles.len() >= self.capacity {
+                break;
+            }
+            let particle = self.spawn(origin);
+            self.particles.push(particle);
+        }
+    }
+
+    fn spawn(&mut self, origin: [f32; 3]) -> Particle {
+        // Random direction inside the cone, built in a basis around `direction`
+        let forward = psp_math::normalize(self.direction);
+        let helper = if forward[1].abs() < 0.99 { [0.0, 1.0, 0.0] } else { [1.0, 0.0, 0.0] };
+        let tangent = psp_math::normalize(psp_math::cross(helper, forward));
+        let bitangent = psp_math::cross(forward, tangent);
+
+        let theta = self.rng.range(0.0, self.spread);
+        let phi = self.rng.range(0.0, 2.0 * PI);
+        let (st, ct) = (psp_math::vfpu_sinf(theta), psp_math::vfpu_cosf(theta));
+        let (sp, cp) = (psp_math::vfpu_sinf(phi), psp_math::vfpu_cosf(phi));
+        let speed = self.rng.range(self.speed.0, self.speed.1);
+
+        let velocity = [0, 1, 2].map(|i| (tangent[i] * st * cp + bitangent[i] * st * sp + forward[i] * ct) * speed);
+        Particle {
+            position: origin,
+            velocity,
+            age: 0.0,
+            lifetime: self.rng.range(self.lifetime.0, self.lifetime.1).max(0.001),
+        }
+    }
+
+    /// Draw every live particle as a camera facing sprite. `view` is the camera's view matrix;
+    /// the model matrix mode has to be active.
+    pub unsafe fn draw(&self, state: &mut RenderState, lists: &mut DisplayLists, view: &ScePspFMatrix4) {
+        if self.particles.is_empty() {
+            return;
+        }
+
+        let texture = match &self.texture {
+            Some(handle) => match handle.upgrade() {
+                Some(texture) => Some(texture),
+                None => {
+                    state.stats.missing_textures += 1;
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        // Camera right and up in world space are the first two rows of the view rotation
+        let right = [view.x.x, view.y.x, view.z.x];
+        let up = [view.x.y, view.y.y, view.z.y];
+
+        let bytes = self.particles.len() * 2 * size_of::<ParticleVertex>();
+        lists.reserve(bytes.div_ceil(4) + 64);
+        let vertices = sys::sceGuGetMemory(bytes as i32) as *mut ParticleVertex;
+
+        let (columns, rows) = self.frames;
+        let frame_count = columns as u32 * rows as u32;
+        let (frame_w, frame_h) = (1.0 / columns as f32, 1.0 / rows as f32);
+
+        for (i, p) in self.particles.iter().enumerate() {
+            let t = p.age / p.lifetime;
+            let color = sample_color(&self.color_over_life, t);
+            let half = sample(&self.size_over_life, t) * 0.5;
+
+            let frame = ((t * frame_count as f32) as u32).min(frame_count - 1);
+            let u = (frame % columns as u32) as f32 * frame_w;
+            let v = (frame / columns as u32) as f32 * frame_h;
+
+            // Opposite corners of the billboard; the GE fills in the screen aligned rectangle
+            let corner = |sx: f32, sy: f32, u, v| {
+                let [x, y, z] = [0, 1, 2].map(|i| p.position[i] + (right[i] * sx + up[i] * sy) * half);
+                ParticleVertex { u, v, color, x, y, z }
+            };
+            vertices.add(i * 2).write(corner(-1.0, 1.0, u, v));
+            vertices.add(i * 2 + 1).write(corner(1.0, -1.0, u + frame_w, v + frame_h));
+        }
+
+        match &texture {
+            Some(texture) => {
+                state.set(GuState::Texture2D, true);
+                state.bind_texture(texture, self.texture_format, self.swizzle);
+                state.tex_map(None);
+                state.tex_func(TextureEffect::Modulate, TextureColorComponent::Rgba);
+            }
+            None => state.set(GuState::Texture2D, false),
+        }
+        state.blend(Some(self.blend));
+
+        sys::sceGumLoadIdentity();
+        let vtype = VertexType::TEXTURE_32BITF | VertexType::COLOR_8888 | VertexType::VERTEX_32BITF | VertexType::TRANSFORM_3D;
+        state.draw(GuPrimitive::Sprites, vtype, self.particles.len() * 2, core::ptr::null(), vertices as *const c_void);
+    }
+}
+
+/// Find the keys either side of `t` and how far between them it is
+fn keyframe<T: Copy>(keys: &[(f32, T)], t: f32) -> Option<(T, T, f32)> {
+    let first = keys.first()?;
+    if t <= first.0 {
+        return Some((first.1, first.1, 0.0));
+    }
+
+    for pair in keys.windows(2) {
+        let ((t0, a), (t1, b)) = (pair[0], pair[1]);
+        if t < t1 {
+            return Some((a, b, (t - t0) / (t1 - t0).max(f32::EPSILON)));
+        }
+    }
+
+    let last = keys[keys.len() - 1].1;
+    Some((last, last, 0.0))
+}
+
+fn sample(keys: &[(f32, f32)], t: f32) -> f32 {
+    keyframe(keys, t).map_or(1.0, |(a, b, f)| a + (b - a) * f)
+}
+
+fn sample_color(keys: &[(f32, u32)], t: f32) -> u32 {
+    keyframe(keys, t).map_or(0xFFFFFFFF, |(a, b, f)| psp_math::lerp_color(a, b, f))
+}
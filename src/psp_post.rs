@@ -0,0 +1,268 @@
+use core::ffi::c_void;
+use core::f32::consts::PI;
+use core::mem::size_of;
+
+use bevy_ecs::resource::Resource;
+use psp::sys::{self, GuPrimitive, GuState, TextureColorComponent, TextureEffect, VertexType};
+use psp::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use crate::psp_math;
+use crate::psp_render::{BlendMode, DisplayLists, Framebuffers, RenderState, RenderTarget, Vram};
+
+/// Bloom works on the frame at a quarter of its size each way
+const BLOOM_WIDTH: u32 = 128;
+const BLOOM_HEIGHT: u32 = 64;
+
+/// Points around the clear middle of the vignette
+const VIGNETTE_SEGMENTS: usize = 32;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ColorVertex {
+    color: u32,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TextureVertex {
+    u: f32,
+    v: f32,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+/// Bright parts of the frame, blurred by scaling down and back up, added on top of it
+pub struct Bloom {
+    pub enabled: bool,
+    /// Subtracted from every channel, so only what is brighter than this glows
+    pub threshold: u8,
+    /// How strongly the glow is added back
+    pub intensity: u8,
+    target: RenderTarget,
+}
+
+impl Bloom {
+    /// Allocates the downsampled target from VRAM, so make it once at startup
+    pub fn new(vram: &Vram) -> Self {
+        Bloom {
+            enabled: true,
+            threshold: 0xA0,
+            intensity: 0xC0,
+            target: RenderTarget::new(vram, BLOOM_WIDTH, BLOOM_HEIGHT, sys::TexturePixelFormat::Psm8888),
+        }
+    }
+}
+
+/// Darkens the edges of the screen
+#[derive(Clone, Copy, Debug)]
+pub struct Vignette {
+    pub enabled: bool,
+    /// ABGR at the very edge; alpha sets how dark it gets
+    pub color: u32,
+    /// Size of the untouched middle, from 0 (none) to 1 (touching the sides)
+    pub radius: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Vignette { enabled: false, color: 0xA0000000, radius: 0.6 }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Fade {
+    from: u32,
+    to: u32,
+    elapsed: f32,
+    duration: f32,
+}
+
+/// Full screen effects run after the world is drawn. Bloom and the vignette apply to the scene
+/// under the HUD; the tint and flash cover everything, so fades take the HUD with them.
+#[derive(Resource, Default)]
+pub struct PostProcess {
+    /// ABGR laid over the frame; alpha sets the strength and zero turns it off
+    pub tint: u32,
+    pub bloom: Option<Bloom>,
+    pub vignette: Vignette,
+    fade: Option<Fade>,
+    /// Color of the current flash at full strength, and seconds left / total
+    flash: (u32, f32, f32),
+}
+
+impl PostProcess {
+    /// Blend the tint to `color` over `seconds`
+    pub fn fade_to(&mut self, color: u32, seconds: f32) {
+        if seconds <= 0.0 {
+            self.tint = color;
+            self.fade = None;
+            return;
+        }
+        self.fade = Some(Fade { from: self.tint, to: color, elapsed: 0.0, duration: seconds });
+    }
+
+    /// Fade the whole screen to black
+    pub fn fade_out(&mut self, seconds: f32) {
+        self.fade_to(0xFF000000, seconds);
+    }
+
+    /// Fade back in from whatever the tint is
+    pub fn fade_in(&mut self, seconds: f32) {
+        self.fade_to(self.tint & 0x00FFFFFF, seconds);
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Cover the screen in `color` and let it die off over `seconds`
+    pub fn flash(&mut self, color: u32, seconds: f32) {
+        self.flash = (color, seconds, seconds);
+    }
+
+    /// Advance fades and flashes by `dt` seconds
+    pub fn tick(&mut self, dt: f32) {
+        if let Some(fade) = &mut self.fade {
+            fade.elapsed += dt;
+            let t = (fade.elapsed / fade.duration).min(1.0);
+            self.tint = psp_math::lerp_color(fade.from, fade.to, t);
+            if t >= 1.0 {
+                self.fade = None;
+            }
+        }
+
+        self.flash.1 = (self.flash.1 - dt).max(0.0);
+    }
+
+    fn flash_color(&self) -> u32 {
+        let (color, remaining, duration) = self.flash;
+        if remaining <= 0.0 || duration <= 0.0 {
+            return 0;
+        }
+        let alpha = ((color >> 24) as f32 * remaining / duration) as u32;
+        (color & 0x00FFFFFF) | (alpha << 24)
+    }
+
+    /// Bloom and vignette. Expects `begin_2d` and the screen bound; leaves the screen bound.
+    pub unsafe fn draw_scene(&self, state: &mut RenderState, lists: &mut DisplayLists, framebuffers: &Framebuffers) {
+        if let Some(bloom) = self.bloom.as_ref().filter(|b| b.enabled) {
+            draw_bloom(state, lists, framebuffers, bloom);
+        }
+
+        if self.vignette.enabled {
+            draw_vignette(state, lists, &self.vignette);
+        }
+    }
+
+    /// Tint and flash. Expects `begin_2d`.
+    pub unsafe fn draw_overlay(&self, state: &mut RenderState, lists: &mut DisplayLists) {
+        for color in [self.tint, self.flash_color()] {
+            if color >> 24 == 0 {
+                continue;
+            }
+            state.set(GuState::Texture2D, false);
+            state.blend(Some(BlendMode::Alpha));
+            fill(state, lists, color, SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32);
+        }
+    }
+}
+
+/// One untextured rectangle from the corner of whatever is bound
+unsafe fn fill(state: &mut RenderState, lists: &mut DisplayLists, color: u32, width: f32, height: f32) {
+    lists.reserve(32);
+    let vertices = sys::sceGuGetMemory(2 * size_of::<ColorVertex>() as i32) as *mut ColorVertex;
+    vertices.write(ColorVertex { color, x: 0.0, y: 0.0, z: 0.0 });
+    vertices.add(1).write(ColorVertex { color, x: width, y: height, z: 0.0 });
+
+    let vtype = VertexType::COLOR_8888 | VertexType::VERTEX_32BITF | VertexType::TRANSFORM_2D;
+    state.draw(GuPrimitive::Sprites, vtype, 2, core::ptr::null(), vertices as *const c_void);
+}
+
+/// One textured rectangle stretching `uv` (in texels) over `size` (in pixels)
+unsafe fn blit(state: &mut RenderState, lists: &mut DisplayLists, uv: [f32; 2], size: [f32; 2]) {
+    lists.reserve(32);
+    let vertices = sys::sceGuGetMemory(2 * size_of::<TextureVertex>() as i32) as *mut TextureVertex;
+    vertices.write(TextureVertex { u: 0.0, v: 0.0, x: 0.0, y: 0.0, z: 0.0 });
+    vertices.add(1).write(TextureVertex { u: uv[0], v: uv[1], x: size[0], y: size[1], z: 0.0 });
+
+    let vtype = VertexType::TEXTURE_32BITF | VertexType::VERTEX_32BITF | VertexType::TRANSFORM_2D;
+    state.draw(GuPrimitive::Sprites, vtype, 2, core::ptr::null(), vertices as *const c_void);
+}
+
+unsafe fn draw_bloom(state: &mut RenderState, lists: &mut DisplayLists, framebuffers: &Framebuffers, bloom: &Bloom) {
+    let target = &bloom.target;
+    let (width, height) = (target.width() as f32, target.height() as f32);
+    let screen = [SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32];
+
+    // Scale the frame down into the target, filtering as it goes. Only the rows of the frame
+    // are read; clamping keeps filtering at the edges from wrapping round to the rest of VRAM.
+    let (frame, format) = framebuffers.draw_texture();
+    target.bind();
+    state.set(GuState::Texture2D, true);
+    state.bind_texture(&frame, format, false);
+    state.tex_wrap(false);
+    state.tex_func(TextureEffect::Replace, TextureColorComponent::Rgb);
+    state.tex_filter(true);
+    state.blend(None);
+    blit(state, lists, screen, [width, height]);
+
+    // Knock everything down by the threshold so only the bright parts are left
+    let threshold = bloom.threshold as u32;
+    state.set(GuState::Texture2D, false);
+    state.blend(Some(BlendMode::Subtract));
+    fill(state, lists, 0xFF000000 | threshold * 0x010101, width, height);
+
+    // Stretch it back over the screen. The texture's alpha is ignored, so the vertex alpha
+    // alone scales what gets added.
+    framebuffers.bind();
+    state.set(GuState::Texture2D, true);
+    state.bind_texture(target.handle(), target.format(), false);
+    state.tex_func(TextureEffect::Modulate, TextureColorComponent::Rgb);
+    state.color(((bloom.intensity as u32) << 24) | 0x00FFFFFF);
+    state.blend(Some(BlendMode::Additive));
+    blit(state, lists, [width, height], screen);
+
+    state.tex_func(TextureEffect::Modulate, TextureColorComponent::Rgba);
+    state.tex_filter(false);
+    state.tex_wrap(true);
+}
+
+unsafe fn draw_vignette(state: &mut RenderState, lists: &mut DisplayLists, vignette: &Vignette) {
+    let count = (VIGNETTE_SEGMENTS + 1) * 2;
+    let bytes = count * size_of::<ColorVertex>();
+    lists.reserve(bytes.div_ceil(4) + 32);
+    let vertices = sys::sceGuGetMemory(bytes as i32) as *mut ColorVertex;
+
+    let (half_w, half_h) = (SCREEN_WIDTH as f32 * 0.5, SCREEN_HEIGHT as f32 * 0.5);
+    let clear = vignette.color & 0x00FFFFFF;
+    let radius = vignette.radius.clamp(0.0, 1.0);
+
+    // A strip between an ellipse in the middle and the edges of the screen
+    for i in 0..=VIGNETTE_SEGMENTS {
+        let angle = i as f32 * 2.0 * PI / VIGNETTE_SEGMENTS as f32;
+        let (c, s) = (psp_math::vfpu_cosf(angle), psp_math::vfpu_sinf(angle));
+        let edge = 1.0 / c.abs().max(s.abs());
+
+        vertices.add(i * 2).write(ColorVertex {
+            color: clear,
+            x: half_w + c * half_w * radius,
+            y: half_h + s * half_h * radius,
+            z: 0.0,
+        });
+        vertices.add(i * 2 + 1).write(ColorVertex {
+            color: vignette.color,
+            x: half_w + c * half_w * edge,
+            y: half_h + s * half_h * edge,
+            z: 0.0,
+        });
+    }
+
+    state.set(GuState::Texture2D, false);
+    state.blend(Some(BlendMode::Alpha));
+    let vtype = VertexType::COLOR_8888 | VertexType::VERTEX_32BITF | VertexType::TRANSFORM_2D;
+    state.draw(GuPrimitive::TriangleStrip, vtype, count, core::ptr::null(), vertices as *const c_void);
+}
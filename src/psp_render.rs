@@ -0,0 +1,1009 @@
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use aligned_vec::{AVec, ConstAlign};
+use alloc::sync::Arc;
+use bevy_ecs::component::Component;
+use bevy_ecs::resource::Resource;
+use hashbrown::HashMap;
+use psp::sys::{
+    self, AlphaFunc, ClearBuffer, DisplayPixelFormat, GuCallbackId, GuContextType, GuPrimitive, GuState, GuTexWrapMode, LightComponent, LightType, MipmapLevel, ScePspFMatrix4, ScePspFVector3, ScePspIMatrix4, ScePspIVector4, TextureColorComponent, TextureEffect, TextureFilter,
+    TextureMapMode, TexturePixelFormat, VertexType
+};
+use psp::vram_alloc::{get_vram_allocator, SimpleVramAllocator};
+use psp::{BUF_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use crate::psp_assets::TextureHandle;
+use crate::psp_math::{self, Frustum};
+
+/// How a fragment is combined with what is already in the framebuffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BlendMode {
+    /// `src * a + dst * (1 - a)`
+    Alpha,
+    /// `src * a + dst`
+    Additive,
+    /// `dst - src`, clamped at zero
+    Subtract,
+}
+
+/// Per-frame counters, reset at the start of every frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub triangles: u32,
+    /// GE state commands that were actually emitted
+    pub state_changes: u32,
+    /// State commands skipped because the GE was already in that state
+    pub state_skips: u32,
+    pub texture_binds: u32,
+    /// Cached lists replayed with `sceGuCallList`
+    pub call_lists: u32,
+    /// Entities that passed / failed the frustum test
+    pub visible: u32,
+    pub culled: u32,
+    /// Draws whose material referenced a texture that has since been unloaded
+    pub missing_textures: u32,
+}
+
+/// Shadow copy of the GE state set during the current frame, so that systems can request state
+/// freely and only differences get written to the display list.
+#[derive(Resource, Default)]
+pub struct RenderState {
+    /// Bitmask of `GuState`s whose value is known
+    known: u32,
+    /// Bitmask of `GuState`s that are enabled (only meaningful where `known` is set)
+    enabled: u32,
+    /// Address, format and swizzle of the bound texture
+    texture: Option<(usize, u32, bool)>,
+    tex_func: Option<(u32, u32)>,
+    tex_filter: Option<bool>,
+    tex_wrap: Option<bool>,
+    tex_transform: Option<[f32; 4]>,
+    /// Lights generating U and V, or `None` for the vertices' own UVs
+    tex_map: Option<Option<(u32, u32)>>,
+    blend: Option<BlendMode>,
+    depth_write: Option<bool>,
+    alpha_ref: Option<u8>,
+    color: Option<u32>,
+    /// Start, end (as bits) and color
+    fog: Option<(u32, u32, u32)>,
+    /// Set when the display has no depth buffer; depth tests and writes then stay off
+    no_depth: bool,
+    /// Set when the display format keeps no stencil bits; the stencil test then stays off
+    no_stencil: bool,
+    pub stats: RenderStats,
+}
+
+impl RenderState {
+    /// Forget everything and reset the counters. Call once per display list.
+    pub fn begin_frame(&mut self) {
+        self.invalidate();
+        self.stats = RenderStats::default();
+    }
+
+    /// Forget the cached state, e.g. after GE commands were sent without going through the cache
+    pub fn invalidate(&mut self) {
+        let stats = self.stats;
+        let (no_depth, no_stencil) = (self.no_depth, self.no_stencil);
+        *self = RenderState { stats, no_depth, no_stencil, ..Default::default() };
+    }
+
+    /// Empty state for drawing to a display set up with `config`
+    pub fn for_display(config: &DisplayConfig) -> Self {
+        RenderState { no_depth: !config.depth, no_stencil: !config.has_stencil(), ..Default::default() }
+    }
+
+    #[inline]
+    fn changed(&mut self, changed: bool) -> bool {
+        if changed {
+            self.stats.state_changes += 1;
+        } else {
+            self.stats.state_skips += 1;
+        }
+        changed
+    }
+
+    /// Enable or disable a GE state
+    pub fn set(&mut self, state: GuState, enabled: bool) {
+        let enabled = enabled
+            && !(self.no_depth && state as u32 == GuState::DepthTest as u32)
+            // The GE reads a missing stencil as 0, which would fail every pixel
+            && !(self.no_stencil && state as u32 == GuState::StencilTest as u32);
+        let bit = 1u32 << state as u32;
+        let differs = self.known & bit == 0 || (self.enabled & bit != 0) != enabled;
+        if !self.changed(differs) {
+            return;
+        }
+
+        self.known |= bit;
+        if enabled {
+            self.enabled |= bit;
+            unsafe { sys::sceGuEnable(state) };
+        } else {
+            self.enabled &= !bit;
+            unsafe { sys::sceGuDisable(state) };
+        }
+    }
+
+    /// Bind `texture` as the current texture
+    pub fn bind_texture(&mut self, texture: &TextureHandle, format: TexturePixelFormat, swizzle: bool) {
+        let key = (texture.raw_bytes() as usize, format as u32, swizzle);
+        if !self.changed(self.texture != Some(key)) {
+            return;
+        }
+
+        self.texture = Some(key);
+        self.stats.texture_binds += 1;
+        unsafe {
+            sys::sceGuTexMode(format, 0, 0, swizzle as i32);
+            sys::sceGuTexImage(
+                MipmapLevel::None,
+                texture.width() as i32,
+                texture.height() as i32,
+                texture.pitch() as i32,
+                texture.raw_bytes() as *const c_void,
+            );
+        }
+    }
+
+    pub fn tex_func(&mut self, effect: TextureEffect, component: TextureColorComponent) {
+        let key = (effect as u32, component as u32);
+        if !self.changed(self.tex_func != Some(key)) {
+            return;
+        }
+
+        self.tex_func = Some(key);
+        unsafe { sys::sceGuTexFunc(effect, component) };
+    }
+
+    /// Use linear (`true`) or nearest (`false`) filtering for minification and magnification
+    pub fn tex_filter(&mut self, linear: bool) {
+        if !self.changed(self.tex_filter != Some(linear)) {
+            return;
+        }
+
+        self.tex_filter = Some(linear);
+        unsafe {
+            if linear {
+                sys::sceGuTexFilter(TextureFilter::Linear, TextureFilter::Linear);
+            } else {
+                sys::sceGuTexFilter(TextureFilter::Nearest, TextureFilter::Nearest);
+            }
+        }
+    }
+
+    /// Repeat (`true`) or clamp (`false`) texture coordinates outside the texture
+    pub fn tex_wrap(&mut self, repeat: bool) {
+        if !self.changed(self.tex_wrap != Some(repeat)) {
+            return;
+        }
+
+        self.tex_wrap = Some(repeat);
+        let mode = || if repeat { GuTexWrapMode::Repeat } else { GuTexWrapMode::Clamp };
+        unsafe { sys::sceGuTexWrap(mode(), mode()) };
+    }
+
+    /// Scale and offset applied to texture coordinates
+    pub fn tex_transform(&mut self, scale_u: f32, scale_v: f32, offset_u: f32, offset_v: f32) {
+        let key = [scale_u, scale_v, offset_u, offset_v];
+        if !self.changed(self.tex_transform != Some(key)) {
+            return;
+        }
+
+        self.tex_transform = Some(key);
+        unsafe {
+            sys::sceGuTexScale(scale_u, scale_v);
+            sys::sceGuTexOffset(offset_u, offset_v);
+        }
+    }
+
+    /// Take texture coordinates from the vertices (`None`), or generate them from the normal dotted
+    /// with the positions of two lights
+    pub fn tex_map(&mut self, lights: Option<(u32, u32)>) {
+        if !self.changed(self.tex_map != Some(lights)) {
+            return;
+        }
+
+        self.tex_map = Some(lights);
+        unsafe {
+            match lights {
+                Some((u, v)) => sys::sceGuTexMapMode(TextureMapMode::EnvironmentMap, u, v),
+                None => sys::sceGuTexMapMode(TextureMapMode::TextureCoords, 0, 0),
+            }
+        }
+    }
+
+    /// Enable blending with the given mode, or disable it with `None`
+    pub fn blend(&mut self, mode: Option<BlendMode>) {
+        let Some(mode) = mode else {
+            self.set(GuState::Blend, false);
+            return;
+        };
+
+        if self.changed(self.blend != Some(mode)) {
+            self.blend = Some(mode);
+            unsafe {
+                match mode {
+                    BlendMode::Alpha => sys::sceGuBlendFunc(sys::BlendOp::Add, sys::BlendFactor::SrcAlpha, sys::BlendFactor::OneMinusSrcAlpha, 0, 0),
+                    BlendMode::Additive => sys::sceGuBlendFunc(sys::BlendOp::Add, sys::BlendFactor::SrcAlpha, sys::BlendFactor::Fix, 0, 0xffffffff),
+                    BlendMode::Subtract => sys::sceGuBlendFunc(sys::BlendOp::ReverseSubtract, sys::BlendFactor::Fix, sys::BlendFactor::Fix, 0xffffffff, 0xffffffff),
+                }
+            }
+        }
+
+        self.set(GuState::Blend, true);
+    }
+
+    /// Enable or disable writes to the depth buffer
+    pub fn depth_write(&mut self, enabled: bool) {
+        let enabled = enabled && !self.no_depth;
+        if !self.changed(self.depth_write != Some(enabled)) {
+            return;
+        }
+
+        self.depth_write = Some(enabled);
+        // A set mask bit disables depth writes
+        unsafe { sys::sceGuDepthMask(!enabled as i32) };
+    }
+
+    /// Discard fragments whose alpha is at or below `cutoff`, or disable the test with `None`
+    pub fn alpha_test(&mut self, cutoff: Option<u8>) {
+        let Some(cutoff) = cutoff else {
+            self.set(GuState::AlphaTest, false);
+            return;
+        };
+
+        if self.changed(self.alpha_ref != Some(cutoff)) {
+            self.alpha_ref = Some(cutoff);
+            unsafe { sys::sceGuAlphaFunc(AlphaFunc::Greater, cutoff as i32, 0xff) };
+        }
+
+        self.set(GuState::AlphaTest, true);
+    }
+
+    /// Set the color used for vertices without a color component
+    pub fn color(&mut self, color: u32) {
+        if !self.changed(self.color != Some(color)) {
+            return;
+        }
+
+        self.color = Some(color);
+        unsafe { sys::sceGuColor(color) };
+    }
+
+    /// Enable fog with the given range and color, or disable it
+    pub fn fog(&mut self, fog: &Fog) {
+        self.set(GuState::Fog, fog.enabled);
+        if !fog.enabled {
+            return;
+        }
+
+        let key = (fog.start.to_bits(), fog.end.to_bits(), fog.color);
+        if !self.changed(self.fog != Some(key)) {
+            return;
+        }
+
+        self.fog = Some(key);
+        unsafe { sys::sceGuFog(fog.start, fog.end, fog.color) };
+    }
+
+    /// Draw through `sceGumDrawArray`, counting the call
+    pub unsafe fn draw(&mut self, prim: GuPrimitive, vtype: VertexType, count: usize, indices: *const c_void, vertices: *const c_void) {
+        self.stats.draw_calls += 1;
+        self.stats.triangles += triangle_count(prim, count);
+
+        sys::sceGumDrawArray(prim, vtype, count as i32, indices, vertices);
+    }
+}
+
+/// Lights whose positions become the U and V axes of sphere mapped materials. They are never
+/// enabled, so they don't light anything.
+pub const SPHERE_MAP_LIGHTS: (u32, u32) = (2, 3);
+
+/// Point the sphere map axes along the camera's right and down, so reflections follow the view
+pub unsafe fn sphere_map_axes(view: &ScePspFMatrix4) {
+    let right = ScePspFVector3 { x: view.x.x, y: view.y.x, z: view.z.x };
+    let down = ScePspFVector3 { x: -view.x.y, y: -view.y.y, z: -view.z.y };
+    sys::sceGuLight(SPHERE_MAP_LIGHTS.0 as i32, LightType::Directional, LightComponent::DIFFUSE, &right);
+    sys::sceGuLight(SPHERE_MAP_LIGHTS.1 as i32, LightType::Directional, LightComponent::DIFFUSE, &down);
+}
+
+/// Put the GE into the state screen space overlays are drawn with: alpha blended, nearest
+/// filtered, unculled and on top of everything else
+pub fn begin_2d(state: &mut RenderState) {
+    state.set(GuState::Fog, false);
+    state.set(GuState::DepthTest, false);
+    state.set(GuState::CullFace, false);
+    state.depth_write(false);
+    state.alpha_test(None);
+    state.blend(Some(BlendMode::Alpha));
+    state.tex_func(TextureEffect::Modulate, TextureColorComponent::Rgba);
+    state.tex_filter(false);
+}
+
+/// Undo `begin_2d` for whatever gets drawn after it
+pub fn end_2d(state: &mut RenderState) {
+    state.set(GuState::DepthTest, true);
+    state.set(GuState::CullFace, true);
+    state.depth_write(true);
+    state.blend(None);
+}
+
+/// Number of triangles `count` vertices produce for a given primitive
+pub fn triangle_count(prim: GuPrimitive, count: usize) -> u32 {
+    match prim {
+        GuPrimitive::Triangles => (count / 3) as u32,
+        GuPrimitive::TriangleStrip | GuPrimitive::TriangleFan => count.saturating_sub(2) as u32,
+        _ => 0,
+    }
+}
+
+/// Visibility settings, and the frustum of the camera being rendered
+#[derive(Resource)]
+pub struct Culling {
+    /// Skip entities whose bounding sphere lies outside the camera frustum
+    pub frustum_culling: bool,
+    /// Wrap draws in `sceGuBeginObject`/`sceGuEndObject` so the GE rejects off-screen meshes
+    /// itself. This also covers recorded static lists, which the CPU can't cull.
+    pub ge_bounding_box: bool,
+    pub frustum: Frustum,
+    /// `projection * view` the frustum was built from
+    pub view_projection: ScePspFMatrix4,
+    /// The view matrix alone, for things that face the camera
+    pub view: ScePspFMatrix4,
+}
+
+impl Default for Culling {
+    fn default() -> Self {
+        Culling {
+            frustum_culling: true,
+            ge_bounding_box: false,
+            frustum: Frustum::default(),
+            view_projection: psp_math::mat4_identity(),
+            view: psp_math::mat4_identity(),
+        }
+    }
+}
+
+/// Linear distance fog, fading geometry into `color` between `start` and `end`. As a resource it
+/// applies to every camera; a `Fog` on the camera entity overrides it.
+#[derive(Resource, Component, Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub enabled: bool,
+    pub start: f32,
+    pub end: f32,
+    /// ABGR; also used as the clear color while fog is enabled
+    pub color: u32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Fog { enabled: false, start: 20.0, end: 40.0, color: 0xff554433 }
+    }
+}
+
+impl Fog {
+    pub fn new(start: f32, end: f32, color: u32) -> Self {
+        Fog { enabled: true, start, end, color }
+    }
+}
+
+/// Number of display lists alternated between frames
+pub const LIST_COUNT: usize = 2;
+
+/// Default size of each display list, in words
+pub const DEFAULT_LIST_WORDS: usize = 0x20000;
+
+/// System time (low 32 bits, microseconds) at which the GE last finished a list. Written from the
+/// GE finish interrupt.
+static GE_FINISHED: AtomicU32 = AtomicU32::new(0);
+
+extern "C" fn on_ge_finish(_id: i32, _arg: *mut c_void) {
+    GE_FINISHED.store(unsafe { sys::sceKernelGetSystemTimeLow() }, Ordering::Relaxed);
+}
+
+/// How long the CPU and GE spent on the last frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameTiming {
+    /// Time the CPU spent working on the last frame, excluding `wait_us`
+    pub cpu_us: u32,
+    /// Time the GE spent executing the last frame's lists
+    pub gpu_us: u32,
+    /// Time the CPU spent blocked on the GE fence and vblank
+    pub wait_us: u32,
+    /// Time between the last two kicks
+    pub frame_us: u32,
+}
+
+/// How much display list space frames are using
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ListUsage {
+    /// Bytes written during the last frame, across all splits
+    pub bytes: usize,
+    /// Largest number of bytes a single list has held
+    pub peak_bytes: usize,
+    /// Times the last frame had to be split across lists to avoid an overflow
+    pub splits: u32,
+}
+
+/// Owns the display lists frames are recorded into.
+///
+/// Frame N is kicked to the GE at the end of the render schedule and only waited on when frame
+/// N + 1 starts rendering, so the update schedule in between runs while the GE is drawing. Lists
+/// are alternated so one is never rewritten while the GE may still be reading it.
+#[derive(Resource)]
+pub struct DisplayLists {
+    lists: [AVec<u32, ConstAlign<16>>; LIST_COUNT],
+    /// Index of the list currently being recorded
+    current: usize,
+    /// Lists used so far this frame
+    used_this_frame: usize,
+    /// Whether a kicked list has not been waited on yet
+    pending: bool,
+    kicked_at: u32,
+    pub timing: FrameTiming,
+    pub usage: ListUsage,
+}
+
+impl Default for DisplayLists {
+    fn default() -> Self {
+        DisplayLists::new(DEFAULT_LIST_WORDS)
+    }
+}
+
+impl DisplayLists {
+    /// Allocate `LIST_COUNT` lists of `words` words each
+    pub fn new(words: usize) -> Self {
+        let list = || {
+            let mut buf = AVec::with_capacity(16, words);
+            buf.resize(words, 0);
+            buf
+        };
+
+        DisplayLists {
+            lists: core::array::from_fn(|_| list()),
+            current: 0,
+            used_this_frame: 0,
+            pending: false,
+            kicked_at: 0,
+            timing: FrameTiming::default(),
+            usage: ListUsage::default(),
+        }
+    }
+
+    /// Install the GE finish callback used to time lists
+    pub fn install() {
+        unsafe { sys::sceGuSetCallback(GuCallbackId::Finish, Some(on_ge_finish)) };
+    }
+
+    /// Size of each list in bytes
+    pub fn capacity(&self) -> usize {
+        self.lists[0].len() * 4
+    }
+
+    /// Start recording into list `index`
+    pub unsafe fn start(&mut self, index: usize) {
+        self.current = index % LIST_COUNT;
+        sys::sceGuStart(GuContextType::Direct, self.lists[self.current].as_mut_ptr() as *mut c_void);
+    }
+
+    /// Block until the GE has finished all kicked lists and time the last frame. Returns false if
+    /// nothing was pending.
+    pub fn wait(&mut self) -> bool {
+        if !self.sync() {
+            return false;
+        }
+
+        self.timing.gpu_us = GE_FINISHED.load(Ordering::Relaxed).wrapping_sub(self.kicked_at);
+        true
+    }
+
+    /// Block until the GE has finished all kicked lists, leaving the timing alone
+    fn sync(&mut self) -> bool {
+        if !self.pending {
+            return false;
+        }
+
+        unsafe { sys::sceGuSync(sys::GuSyncMode::Finish, sys::GuSyncBehavior::Wait) };
+        self.pending = false;
+        true
+    }
+
+    /// Record that time from `since` until now was spent waiting rather than working
+    pub fn waited_since(&mut self, since: u32) {
+        self.timing.wait_us = unsafe { sys::sceKernelGetSystemTimeLow() }.wrapping_sub(since);
+    }
+
+    /// Start a new frame in the next list
+    pub unsafe fn begin_frame(&mut self) {
+        self.usage.bytes = 0;
+        self.usage.splits = 0;
+        self.used_this_frame = 1;
+        self.start(self.current + 1);
+    }
+
+    /// Bytes written to the current list so far
+    pub fn used(&self) -> usize {
+        unsafe { sys::sceGuCheckList() as usize }
+    }
+
+    /// Close the current list and hand it to the GE without waiting for it
+    unsafe fn submit(&mut self) {
+        let bytes = sys::sceGuFinish() as usize;
+        self.usage.bytes += bytes;
+        self.usage.peak_bytes = self.usage.peak_bytes.max(bytes);
+        self.pending = true;
+    }
+
+    /// Make sure at least `words` more words fit into the current list. If they don't, the list
+    /// is kicked early and recording continues in the next one.
+    pub unsafe fn reserve(&mut self, words: usize) {
+        // Leave room for the finish/end commands
+        if self.used() + (words + 4) * 4 <= self.capacity() {
+            return;
+        }
+
+        self.submit();
+        self.usage.splits += 1;
+
+        // Every list has been used this frame; the next one may still be executing. Mid-frame
+        // `kicked_at` still belongs to the last frame, so this wait isn't timed.
+        if self.used_this_frame == LIST_COUNT {
+            self.sync();
+            self.used_this_frame = 0;
+        }
+        self.used_this_frame += 1;
+        self.start(self.current + 1);
+    }
+
+    /// Close the frame's list and hand it to the GE without waiting for it
+    pub unsafe fn kick(&mut self) {
+        self.submit();
+
+        let now = sys::sceKernelGetSystemTimeLow();
+        self.timing.frame_us = now.wrapping_sub(self.kicked_at);
+        self.timing.cpu_us = self.timing.frame_us.saturating_sub(self.timing.wait_us);
+        self.kicked_at = now;
+    }
+
+    /// Whether the last frame came close to (or past) the size of a single list
+    pub fn near_capacity(&self) -> bool {
+        self.usage.splits > 0 || self.usage.peak_bytes * 4 > self.capacity() * 3
+    }
+}
+
+/// A display list recorded once and replayed every frame with `sceGuCallList`
+pub struct CallList {
+    buf: AVec<u32, ConstAlign<16>>,
+    /// Words written by the last recording
+    used: usize,
+}
+
+impl CallList {
+    /// Words reserved per recorded draw. A draw with full material state, a model matrix, a
+    /// bounding box test, eight morph weights and a toon outline comes to under 100.
+    pub const WORDS_PER_DRAW: usize = 128;
+
+    /// Allocate a list big enough to hold `draws` mesh draws
+    pub fn with_draws(draws: usize) -> Self {
+        let words = draws.max(1) * Self::WORDS_PER_DRAW;
+        let mut buf = AVec::with_capacity(16, words);
+        buf.resize(words, 0);
+        CallList { buf, used: 0 }
+    }
+
+    /// Number of draws this list has room for
+    pub fn capacity(&self) -> usize {
+        self.buf.len() / Self::WORDS_PER_DRAW
+    }
+
+    /// Bytes used by the last recording
+    pub fn size(&self) -> usize {
+        self.used * 4
+    }
+
+    /// Record everything `f` sends to the GE into this list instead of the current one. `f` has to
+    /// call [`Recording::reserve`] before each draw and stop once it returns false; the list is
+    /// then doubled and `f` runs again from the start.
+    pub unsafe fn record(&mut self, mut f: impl FnMut(&mut Recording)) {
+        loop {
+            sys::sceGuStart(GuContextType::Call, self.buf.as_mut_ptr() as *mut c_void);
+            let mut recording = Recording { capacity: self.buf.len() * 4, overflowed: false };
+            f(&mut recording);
+            self.used = sys::sceGuFinish() as usize / 4;
+
+            if !recording.overflowed {
+                return;
+            }
+            let words = self.buf.len() * 2;
+            self.buf.resize(words, 0);
+        }
+    }
+
+    /// Run the recorded list from the current display list
+    pub unsafe fn call(&self) {
+        sys::sceGuCallList(self.buf.as_ptr() as *const c_void);
+    }
+}
+
+/// Space left in a [`CallList`] being recorded
+pub struct Recording {
+    /// Size of the list in bytes
+    capacity: usize,
+    overflowed: bool,
+}
+
+impl Recording {
+    /// Whether `words` more words fit into the list. Once this returns false the recording is
+    /// thrown away, so nothing else should be drawn into it.
+    pub fn reserve(&mut self, words: usize) -> bool {
+        // Leave room for the finish commands
+        let fits = !self.overflowed && unsafe { sys::sceGuCheckList() } as usize + (words + 4) * 4 <= self.capacity;
+        self.overflowed |= !fits;
+        fits
+    }
+}
+
+/// Caches an entity's draw in a [`CallList`]. The list is recorded the first time the entity is
+/// rendered and again whenever its `Mesh`, `Material` or `Transform` changes. Only used for
+/// non-blended materials; transparent draws still need to be sorted every frame.
+#[derive(Component, Default)]
+pub struct StaticDraw {
+    pub(crate) list: Option<CallList>,
+    pub(crate) dirty: bool,
+    /// Unloaded textures the list was recorded without
+    pub(crate) missing_textures: u32,
+}
+
+impl StaticDraw {
+    pub fn is_ready(&self) -> bool {
+        self.list.is_some() && !self.dirty
+    }
+}
+
+/// Records every entity sharing an id into a single [`CallList`], stored in [`StaticGroups`].
+/// The whole group is re-recorded when any member changes, joins or leaves.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StaticGroup(pub u32);
+
+/// A recorded [`StaticGroup`]
+pub struct GroupList {
+    pub list: CallList,
+    /// Unloaded textures the list was recorded without
+    pub missing_textures: u32,
+}
+
+/// Recorded lists for each [`StaticGroup`]
+#[derive(Resource, Default)]
+pub struct StaticGroups {
+    pub(crate) lists: HashMap<StaticGroup, GroupList>,
+}
+
+impl StaticGroups {
+    /// Drop the recording of `group` so it is recorded again next frame
+    pub fn invalidate(&mut self, group: StaticGroup) {
+        self.lists.remove(&group);
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.lists.clear();
+    }
+
+    pub fn is_ready(&self, group: StaticGroup) -> bool {
+        self.lists.contains_key(&group)
+    }
+}
+
+/// How the screen is set up, read once by `init_Gu`. 16-bit formats halve the framebuffers,
+/// leaving more VRAM for render targets. They keep fewer stencil bits in alpha: 4 for `Psm4444`,
+/// 1 for `Psm5551` and none for `Psm5650`. Without a stencil the shadow pass draws untested, so
+/// overlapping shadows darken the ground twice.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct DisplayConfig {
+    pub format: DisplayPixelFormat,
+    /// Allocate a depth buffer and test against it. Without one, draws land in submission order.
+    pub depth: bool,
+    /// Ordered dithering, which hides banding in 16-bit output
+    pub dither: bool,
+    /// Wait for vertical blank before swapping
+    pub vsync: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig { format: DisplayPixelFormat::Psm8888, depth: true, dither: false, vsync: true }
+    }
+}
+
+impl DisplayConfig {
+    /// 16-bit color, dithered
+    pub fn rgb565() -> Self {
+        DisplayConfig { format: DisplayPixelFormat::Psm5650, dither: true, ..Default::default() }
+    }
+
+    /// Whether the color buffers keep stencil bits in their alpha
+    pub fn has_stencil(&self) -> bool {
+        !matches!(self.format, DisplayPixelFormat::Psm5650)
+    }
+
+    /// Format of the color buffers, as textures are allocated
+    pub fn texture_format(&self) -> TexturePixelFormat {
+        texture_format(self.format)
+    }
+
+    /// Set up dithering as configured
+    pub unsafe fn apply_dither(&self) {
+        if !self.dither {
+            sys::sceGuDisable(GuState::Dither);
+            return;
+        }
+
+        // 4x4 Bayer matrix, centered on zero
+        let row = |x, y, z, w| ScePspIVector4 { x, y, z, w };
+        sys::sceGuSetDither(&ScePspIMatrix4 {
+            x: row(-4, 0, -3, 1),
+            y: row(2, -2, 3, -1),
+            z: row(-3, 1, -4, 0),
+            w: row(3, -1, 2, -2),
+        });
+        sys::sceGuEnable(GuState::Dither);
+    }
+}
+
+/// Owner of the VRAM bump allocator. Everything carved out of it stays allocated for the rest of
+/// the program, so framebuffers and render targets should be set up once at startup.
+#[derive(Resource)]
+pub struct Vram(SimpleVramAllocator);
+
+impl Vram {
+    /// Take the allocator; panics if something else already has it
+    pub fn take() -> Self {
+        Vram(get_vram_allocator().expect("VRAM allocator already taken"))
+    }
+
+    /// Allocate an image, returning its offset from the start of VRAM (what the framebuffer and
+    /// depth buffer functions take) and its absolute address (what textures take)
+    pub fn alloc_texture(&self, width: u32, height: u32, format: TexturePixelFormat) -> (*mut u8, *mut u8) {
+        let chunk = self.0.alloc_texture_pixels(width, height, format);
+        (chunk.as_mut_ptr_from_zero(), chunk.as_mut_ptr_direct_to_vram())
+    }
+}
+
+/// The screen's framebuffers as VRAM offsets. `draw` follows `sceGuSwapBuffers`.
+#[derive(Resource)]
+pub struct Framebuffers {
+    pub draw: *mut c_void,
+    pub display: *mut c_void,
+    pub depth: *mut c_void,
+    pub format: DisplayPixelFormat,
+}
+
+// Only ever touched from the main thread; the pointers are VRAM offsets, not owned memory
+unsafe impl Send for Framebuffers {}
+unsafe impl Sync for Framebuffers {}
+
+impl Default for Framebuffers {
+    fn default() -> Self {
+        Framebuffers {
+            draw: core::ptr::null_mut(),
+            display: core::ptr::null_mut(),
+            depth: core::ptr::null_mut(),
+            format: DisplayPixelFormat::Psm8888,
+        }
+    }
+}
+
+impl Framebuffers {
+    /// Point drawing back at the screen after a render target
+    pub unsafe fn bind(&self) {
+        sys::sceGuDrawBufferList(self.format, self.draw, BUF_WIDTH as i32);
+        bind_viewport(SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+    }
+
+    /// The buffer being drawn this frame as a texture, for effects that sample the frame so far.
+    /// It is declared 512x512 since the GE only takes power of two sizes, but only the top
+    /// `SCREEN_HEIGHT` rows belong to the buffer; the rest is whatever comes next in VRAM. Keep V
+    /// below `SCREEN_HEIGHT` and the wrap mode clamped when sampling it.
+    pub unsafe fn draw_texture(&self) -> (TextureHandle, TexturePixelFormat) {
+        let address = sys::sceGeEdramGetAddr().add(self.draw as usize);
+        let texture = TextureHandle::from_vram(BUF_WIDTH as usize, BUF_WIDTH as usize, BUF_WIDTH as usize, address);
+        (texture, texture_format(self.format))
+    }
+}
+
+/// Center a `width` by `height` viewport on the GE's drawing area and scissor to it
+pub unsafe fn bind_viewport(width: i32, height: i32) {
+    sys::sceGuOffset(2048 - (width as u32 / 2), 2048 - (height as u32 / 2));
+    sys::sceGuViewport(2048, 2048, width, height);
+    sys::sceGuScissor(0, 0, width, height);
+}
+
+/// A texture in VRAM that a `Camera` can draw into. Its `handle` goes into materials like any
+/// other texture. Render targets share the screen's depth buffer, so they can't be wider than
+/// `BUF_WIDTH`, and clear depth before every pass.
+#[derive(Clone)]
+pub struct RenderTarget {
+    handle: Arc<TextureHandle>,
+    format: TexturePixelFormat,
+    offset: *mut u8,
+}
+
+unsafe impl Send for RenderTarget {}
+unsafe impl Sync for RenderTarget {}
+
+impl RenderTarget {
+    /// `width` and `height` have to be powers of two for the target to be sampled as a texture.
+    /// `format` has to be one of the 16 or 32-bit formats the GE can draw to.
+    pub fn new(vram: &Vram, width: u32, height: u32, format: TexturePixelFormat) -> Self {
+        debug_assert!(width.is_power_of_two() && height.is_power_of_two() && width <= BUF_WIDTH);
+        debug_assert!(display_format(format).is_some());
+
+        let (offset, address) = vram.alloc_texture(width, height, format);
+        RenderTarget {
+            handle: Arc::new(TextureHandle::from_vram(width as usize, height as usize, width as usize, address)),
+            format,
+            offset,
+        }
+    }
+
+    pub fn handle(&self) -> &Arc<TextureHandle> {
+        &self.handle
+    }
+
+    pub fn format(&self) -> TexturePixelFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> usize {
+        self.handle.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.handle.height()
+    }
+
+    /// Redirect drawing into this target
+    pub unsafe fn bind(&self) {
+        let format = display_format(self.format).unwrap_or(DisplayPixelFormat::Psm8888);
+        sys::sceGuDrawBufferList(format, self.offset as *mut c_void, self.width() as i32);
+        bind_viewport(self.width() as i32, self.height() as i32);
+    }
+}
+
+/// The texture format matching a framebuffer format
+fn texture_format(format: DisplayPixelFormat) -> TexturePixelFormat {
+    match format {
+        DisplayPixelFormat::Psm5650 => TexturePixelFormat::Psm5650,
+        DisplayPixelFormat::Psm5551 => TexturePixelFormat::Psm5551,
+        DisplayPixelFormat::Psm4444 => TexturePixelFormat::Psm4444,
+        DisplayPixelFormat::Psm8888 => TexturePixelFormat::Psm8888,
+    }
+}
+
+/// The framebuffer format matching a texture format, for those the GE can render to
+fn display_format(format: TexturePixelFormat) -> Option<DisplayPixelFormat> {
+    match format {
+        TexturePixelFormat::Psm5650 => Some(DisplayPixelFormat::Psm5650),
+        TexturePixelFormat::Psm5551 => Some(DisplayPixelFormat::Psm5551),
+        TexturePixelFormat::Psm4444 => Some(DisplayPixelFormat::Psm4444),
+        TexturePixelFormat::Psm8888 => Some(DisplayPixelFormat::Psm8888),
+        _ => None,
+    }
+}
+
+/// Background for cameras that clear their color and don't override it. Fogged views clear to
+/// the fog color instead, so the far plane blends in.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ClearColor(pub u32);
+
+impl Default for ClearColor {
+    fn default() -> Self {
+        ClearColor(0xff554433)
+    }
+}
+
+/// Which buffers a camera clears before drawing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClearFlags {
+    pub color: bool,
+    pub depth: bool,
+    /// Reset the stencil that shadows use to avoid darkening a pixel twice
+    pub stencil: bool,
+}
+
+impl ClearFlags {
+    pub const ALL: ClearFlags = ClearFlags { color: true, depth: true, stencil: true };
+    pub const COLOR: ClearFlags = ClearFlags { color: true, depth: false, stencil: false };
+    pub const DEPTH: ClearFlags = ClearFlags { color: false, depth: true, stencil: false };
+    pub const STENCIL: ClearFlags = ClearFlags { color: false, depth: false, stencil: true };
+    /// Draw over whatever is there, e.g. a screen camera layered over the player's
+    pub const NONE: ClearFlags = ClearFlags { color: false, depth: false, stencil: false };
+
+    /// Clear the bound buffer. Depth is skipped if the display has no depth buffer.
+    pub unsafe fn clear(self, color: u32, config: &DisplayConfig) {
+        let mut buffers = ClearBuffer::empty();
+        if self.color {
+            sys::sceGuClearColor(color);
+            buffers |= ClearBuffer::COLOR_BUFFER_BIT;
+        }
+        if self.depth && config.depth {
+            sys::sceGuClearDepth(0);
+            buffers |= ClearBuffer::DEPTH_BUFFER_BIT;
+        }
+        if self.stencil {
+            // Shadows replace it with 0 on every pixel they darken
+            sys::sceGuClearStencil(0xFF);
+            buffers |= ClearBuffer::STENCIL_BUFFER_BIT;
+        }
+
+        if !buffers.is_empty() {
+            sys::sceGuClear(buffers);
+        }
+    }
+}
+
+impl Default for ClearFlags {
+    fn default() -> Self {
+        ClearFlags::ALL
+    }
+}
+
+/// Projection settings for a view. The player's camera draws to the screen. Other cameras need a
+/// `Transform`; those with a `target` are drawn before the screen each frame, and those without
+/// are layered over the player's view by `order`, clearing only what `clear` asks for.
+#[derive(Component, Clone)]
+pub struct Camera {
+    /// Vertical field of view in degrees
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub target: Option<RenderTarget>,
+    /// Cameras with a `target` always clear depth, since every target shares the screen's depth
+    /// buffer
+    pub clear: ClearFlags,
+    /// Overrides `ClearColor` and the fog color
+    pub clear_color: Option<u32>,
+    /// Screen cameras other than the player's are drawn lowest first
+    pub order: i32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera { fov: 90.0, near: 0.5, far: 40.0, target: None, clear: ClearFlags::ALL, clear_color: None, order: 0 }
+    }
+}
+
+impl Camera {
+    pub fn with_target(mut self, target: RenderTarget) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn with_clear(mut self, clear: ClearFlags) -> Self {
+        self.clear = clear;
+        self
+    }
+
+    pub fn with_clear_color(mut self, color: u32) -> Self {
+        self.clear_color = Some(color);
+        self
+    }
+
+    pub fn with_order(mut self, order: i32) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// What to clear the view's color to, given the resources and the fog the view is drawn with
+    pub fn background(&self, clear_color: &ClearColor, fog: &Fog) -> u32 {
+        self.clear_color.unwrap_or(if fog.enabled { fog.color } else { clear_color.0 })
+    }
+
+    pub fn aspect(&self) -> f32 {
+        match &self.target {
+            Some(target) => target.width() as f32 / target.height() as f32,
+            None => 16.0 / 9.0,
+        }
+    }
+}
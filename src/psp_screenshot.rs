@@ -0,0 +1,155 @@
+use alloc::{format, string::String, vec::Vec};
+use bevy_ecs::resource::Resource;
+use psp::sys::{self, CtrlButtons, DisplayPixelFormat};
+use psp::{BUF_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use crate::print_at;
+use crate::psp_assets::{self, IoError};
+use crate::psp_image;
+use crate::psp_render::Framebuffers;
+
+/// Uncached mirror of VRAM, so the CPU sees what the GE wrote
+const UNCACHED: usize = 0x4000_0000;
+
+/// Seconds the result of a capture stays on screen
+const MESSAGE_SECONDS: f32 = 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Bmp,
+    Png,
+}
+
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// Saves the frame on screen to the memory stick when `combo` is pressed. Files are numbered,
+/// skipping names that are already taken.
+#[derive(Resource)]
+pub struct Screenshot {
+    pub enabled: bool,
+    /// Every one of these has to be held; the capture happens once per press
+    pub combo: CtrlButtons,
+    /// Where files go, with a trailing slash
+    pub directory: String,
+    pub format: ImageFormat,
+    held: bool,
+    next: u32,
+    /// Outcome of the last capture, its color, and seconds left to show it
+    message: Option<(String, u32, f32)>,
+}
+
+impl Default for Screenshot {
+    fn default() -> Self {
+        Screenshot {
+            enabled: cfg!(debug_assertions),
+            combo: CtrlButtons::LTRIGGER | CtrlButtons::RTRIGGER | CtrlButtons::SELECT,
+            directory: String::from("ms0:/PICTURE/"),
+            format: ImageFormat::Bmp,
+            held: false,
+            next: 1,
+            message: None,
+        }
+    }
+}
+
+impl Screenshot {
+    /// Whether the combo was pressed since the last call. Call once per frame.
+    pub fn triggered(&mut self, buttons: CtrlButtons) -> bool {
+        let held = self.enabled && buttons.contains(self.combo);
+        let pressed = held && !self.held;
+        self.held = held;
+        pressed
+    }
+
+    /// Save the displayed buffer, returning the path it went to. The GE must be done with it,
+    /// which it is right after `sceGuSwapBuffers`.
+    pub unsafe fn capture(&mut self, framebuffers: &Framebuffers) -> Result<String, IoError> {
+        let address = sys::sceGeEdramGetAddr().add(framebuffers.display as usize) as usize | UNCACHED;
+        let rgb = to_rgb(address as *const u8, framebuffers.format);
+
+        let data = match self.format {
+            ImageFormat::Bmp => psp_image::encode_bmp(SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize, &rgb),
+            ImageFormat::Png => psp_image::encode_png(SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize, &rgb),
+        };
+
+        psp_assets::create_dir(self.directory.trim_end_matches('/'))?;
+        let path = loop {
+            let path = format!("{}ESO_{:04}.{}", self.directory, self.next, self.format.extension());
+            self.next += 1;
+            if !psp_assets::file_exists(&path) {
+                break path;
+            }
+        };
+
+        psp_assets::write_file(&path, &data)?;
+        Ok(path)
+    }
+
+    /// Show the outcome of a capture for a few seconds
+    pub fn report(&mut self, result: &Result<String, IoError>) {
+        let (text, color) = match result {
+            Ok(path) => (format!("Saved screenshot to {}", path), 0xff00ff00),
+            Err(err) => (format!("Screenshot failed: {:?}", err), 0xff0000ff),
+        };
+        self.message = Some((text, color, MESSAGE_SECONDS));
+    }
+
+    /// Count down the message by `dt` seconds
+    pub fn tick(&mut self, dt: f32) {
+        if let Some((_, _, remaining)) = &mut self.message {
+            *remaining -= dt;
+            if *remaining <= 0.0 {
+                self.message = None;
+            }
+        }
+    }
+
+    /// Print the last capture's outcome along the bottom of the screen, below the debug warnings
+    pub fn draw(&self) {
+        if let Some((text, color, _)) = &self.message {
+            print_at!(0, SCREEN_HEIGHT - 8, *color, "{}", text);
+        }
+    }
+}
+
+/// Expand a `BUF_WIDTH` wide framebuffer into tightly packed RGB8 rows
+unsafe fn to_rgb(pixels: *const u8, format: DisplayPixelFormat) -> Vec<u8> {
+    let (width, height) = (SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize);
+    let mut rgb = Vec::with_capacity(width * height * 3);
+
+    // Widen an n-bit channel to 8 bits by repeating its top bits
+    let widen = |value: u16, bits: u32| {
+        let v = (value as u32 & ((1 << bits) - 1)) << (8 - bits);
+        (v | (v >> bits)) as u8
+    };
+
+    for y in 0..height {
+        let row = y * BUF_WIDTH as usize;
+        for x in 0..width {
+            let pixel = match format {
+                DisplayPixelFormat::Psm8888 => {
+                    let p = pixels.add((row + x) * 4);
+                    [*p, *p.add(1), *p.add(2)]
+                }
+                _ => {
+                    let p = (pixels as *const u16).add(row + x).read();
+                    match format {
+                        DisplayPixelFormat::Psm5650 => [widen(p, 5), widen(p >> 5, 6), widen(p >> 11, 5)],
+                        DisplayPixelFormat::Psm5551 => [widen(p, 5), widen(p >> 5, 5), widen(p >> 10, 5)],
+                        _ => [widen(p, 4), widen(p >> 4, 4), widen(p >> 8, 4)],
+                    }
+                }
+            };
+            rgb.extend_from_slice(&pixel);
+        }
+    }
+
+    rgb
+}
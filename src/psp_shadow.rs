@@ -0,0 +1,191 @@
+use core::ffi::c_void;
+use core::f32::consts::PI;
+use core::mem::size_of;
+
+use bevy_ecs::component::Component;
+use psp::sys::{self, GuPrimitive, GuState, ScePspFMatrix4, ScePspFVector3, ScePspFVector4, StencilFunc, StencilOperation, VertexType};
+
+use crate::psp_geometry::Mesh;
+use crate::psp_math;
+use crate::psp_render::{BlendMode, DisplayLists, RenderState};
+
+/// Segments around the edge of a blob shadow
+const BLOB_SEGMENTS: usize = 12;
+
+/// Shadows sit this far above the ground so they don't fight it for depth
+const GROUND_OFFSET: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShadowKind {
+    /// Soft disc under the object, fading out the higher it is above the ground
+    Blob { radius: f32, fade_height: f32 },
+    /// The mesh itself flattened onto the ground along the light direction
+    Planar,
+}
+
+/// Casts a shadow from the primary `DirectionalLight` onto a horizontal ground plane
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CastsShadow {
+    pub kind: ShadowKind,
+    /// Height of the plane the shadow lands on
+    pub ground: f32,
+    /// ABGR; alpha sets how dark the shadow is
+    pub color: u32,
+}
+
+impl CastsShadow {
+    pub fn blob(radius: f32) -> Self {
+        CastsShadow { kind: ShadowKind::Blob { radius, fade_height: 4.0 }, ground: 0.0, color: 0x80000000 }
+    }
+
+    pub fn planar() -> Self {
+        CastsShadow { kind: ShadowKind::Planar, ground: 0.0, color: 0x80000000 }
+    }
+
+    pub fn with_ground(mut self, ground: f32) -> Self {
+        self.ground = ground;
+        self
+    }
+
+    pub fn with_color(mut self, color: u32) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BlobVertex {
+    color: u32,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+/// Set up blending and the stencil test for shadows. The stencil lives in the framebuffer alpha,
+/// which opaque geometry leaves at 0xFF; every shadow pixel resets it to 0 so overlapping shadows
+/// only darken the ground once. `RenderState` keeps the test off on displays without a stencil.
+pub unsafe fn begin_shadows(state: &mut RenderState) {
+    state.set(GuState::Texture2D, false);
+    state.set(GuState::CullFace, false);
+    state.set(GuState::StencilTest, true);
+    state.alpha_test(None);
+    state.blend(Some(BlendMode::Alpha));
+    state.depth_write(false);
+
+    sys::sceGuStencilFunc(StencilFunc::NotEqual, 0, 0xFF);
+    sys::sceGuStencilOp(StencilOperation::Keep, StencilOperation::Keep, StencilOperation::Replace);
+}
+
+pub fn end_shadows(state: &mut RenderState) {
+    state.set(GuState::StencilTest, false);
+    state.set(GuState::CullFace, true);
+    state.depth_write(true);
+    state.blend(None);
+}
+
+/// Where a ray from `point` along `light` hits the plane `y = ground`, if it does
+fn project(point: [f32; 3], light: [f32; 3], ground: f32) -> Option<[f32; 3]> {
+    if light[1] >= -f32::EPSILON {
+        return None;
+    }
+    let t = (ground - point[1]) / light[1];
+    Some([point[0] + light[0] * t, ground, point[2] + light[2] * t])
+}
+
+/// Draw one shadow for a mesh at `translation`/`rotation`. `light` is the primary light's
+/// direction. Expects `begin_shadows` and the model matrix mode.
+pub unsafe fn draw_shadow(
+    state: &mut RenderState,
+    lists: &mut DisplayLists,
+    shadow: &CastsShadow,
+    mesh: &Mesh,
+    translation: &ScePspFVector3,
+    rotation: &ScePspFVector3,
+    light: [f32; 3],
+) {
+    let ground = shadow.ground + GROUND_OFFSET;
+    let origin = [translation.x, translation.y, translation.z];
+
+    // Vertex colors would override the shadow color, so those meshes fall back to a blob
+    let kind = match shadow.kind {
+        ShadowKind::Planar if mesh.layout.color.is_some() => {
+            ShadowKind::Blob { radius: mesh.sphere.radius, fade_height: 4.0 }
+        }
+        kind => kind,
+    };
+
+    match kind {
+        ShadowKind::Blob { radius, fade_height } => {
+            let center = psp_math::rotate_xyz(mesh.sphere.center, rotation);
+            let center = [0, 1, 2].map(|i| center[i] + origin[i]);
+            let Some(hit) = project(center, light, ground) else {
+                return;
+            };
+
+            // Fade out with height above the ground
+            let height = (center[1] - ground).max(0.0);
+            let fade = (1.0 - height / fade_height.max(f32::EPSILON)).clamp(0.0, 1.0);
+            let alpha = ((shadow.color >> 24) as f32 * fade) as u32;
+            if alpha == 0 {
+                return;
+            }
+
+            let count = BLOB_SEGMENTS + 2;
+            let bytes = count * size_of::<BlobVertex>();
+            lists.reserve(bytes.div_ceil(4) + 32);
+            let vertices = sys::sceGuGetMemory(bytes as i32) as *mut BlobVertex;
+
+            let rgb = shadow.color & 0x00FFFFFF;
+            vertices.write(BlobVertex { color: rgb | (alpha << 24), x: hit[0], y: hit[1], z: hit[2] });
+            for i in 0..=BLOB_SEGMENTS {
+                let angle = i as f32 * 2.0 * PI / BLOB_SEGMENTS as f32;
+                vertices.add(i + 1).write(BlobVertex {
+                    color: rgb,
+                    x: hit[0] + psp_math::vfpu_cosf(angle) * radius,
+                    y: hit[1],
+                    z: hit[2] + psp_math::vfpu_sinf(angle) * radius,
+                });
+            }
+
+            sys::sceGumLoadIdentity();
+            let vtype = VertexType::COLOR_8888 | VertexType::VERTEX_32BITF | VertexType::TRANSFORM_3D;
+            state.draw(GuPrimitive::TriangleFan, vtype, count, core::ptr::null(), vertices as *const c_void);
+        }
+        ShadowKind::Planar => {
+            if light[1] >= -f32::EPSILON {
+                return;
+            }
+
+            // Slide every point along the light onto the ground:
+            // p' = p - light * (p.y - ground) / light.y
+            let (sx, sz) = (light[0] / light[1], light[2] / light[1]);
+            let col = |x, y, z, w| ScePspFVector4 { x, y, z, w };
+            let flatten = ScePspFMatrix4 {
+                x: col(1.0, 0.0, 0.0, 0.0),
+                y: col(-sx, 0.0, -sz, 0.0),
+                z: col(0.0, 0.0, 1.0, 0.0),
+                w: col(sx * ground, ground, sz * ground, 1.0),
+            };
+
+            sys::sceGumLoadMatrix(&flatten);
+            sys::sceGumTranslate(translation);
+            sys::sceGumRotateXYZ(rotation);
+            if mesh.scale != 1.0 {
+                sys::sceGumScale(&ScePspFVector3 { x: mesh.scale, y: mesh.scale, z: mesh.scale });
+            }
+
+            // Only positions matter; the texture is off and the color comes from sceGuColor
+            lists.reserve(64);
+            state.color(shadow.color);
+            let indices = mesh.indices.as_ref().map_or(core::ptr::null(), |i| i.as_ptr() as *const _);
+            state.draw(
+                mesh.primitive_type,
+                mesh.vertex_type() | VertexType::TRANSFORM_3D,
+                mesh.draw_count(),
+                indices,
+                mesh.vertices.as_ptr() as *const _,
+            );
+        }
+    }
+}
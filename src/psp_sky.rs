@@ -0,0 +1,185 @@
+use core::f32::consts::PI;
+
+use aligned_vec::{AVec, ConstAlign};
+use alloc::{sync::{Arc, Weak}, vec::Vec};
+use bevy_ecs::resource::Resource;
+use psp::sys::{self, GuState, ScePspFVector3, TextureColorComponent, TextureEffect, TexturePixelFormat, VertexType};
+
+use crate::psp_assets::TextureHandle;
+use crate::psp_geometry::{Mesh, Precision, VertexAttributes, VertexColor, VertexLayout};
+use crate::psp_math;
+use crate::psp_render::RenderState;
+
+/// Half the size of the sky cube / radius of the dome. Has to stay inside the far plane.
+const SKY_RADIUS: f32 = 20.0;
+
+const DOME_RINGS: usize = 8;
+const DOME_SEGMENTS: usize = 16;
+
+/// Cube face directions, in the order faces are passed to `Skybox::cube`
+const FACES: [([f32; 3], [f32; 3]); 6] = [
+    // (forward, up) as seen from inside the cube
+    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+];
+
+/// A texture the sky samples from
+#[derive(Clone)]
+pub struct SkyTexture {
+    pub handle: Weak<TextureHandle>,
+    pub format: TexturePixelFormat,
+    pub swizzle: bool,
+}
+
+impl SkyTexture {
+    pub fn new(handle: &Arc<TextureHandle>, format: TexturePixelFormat, swizzle: bool) -> Self {
+        SkyTexture { handle: Arc::downgrade(handle), format, swizzle }
+    }
+}
+
+pub enum Sky {
+    /// Vertex colored dome fading from `horizon` to `zenith`; below the horizon stays `horizon`
+    Gradient { horizon: u32, zenith: u32 },
+    /// One texture per face: +X, -X, +Y, -Y, +Z, -Z
+    Cube([SkyTexture; 6]),
+    /// A single equirectangular texture wrapped around a sphere
+    Dome(SkyTexture),
+}
+
+/// Background drawn before anything else, centered on the camera so it never gets closer
+#[derive(Resource)]
+pub struct Skybox {
+    pub sky: Sky,
+    /// One mesh per cube face, or a single dome
+    meshes: Vec<Mesh>,
+}
+
+impl Skybox {
+    pub fn gradient(horizon: u32, zenith: u32) -> Self {
+        let layout = VertexLayout::new(Precision::Float).with_color(VertexColor::Rgba8888);
+        let dome = dome(layout, |elevation| psp_math::lerp_color(horizon, zenith, elevation.max(0.0)));
+        Skybox { sky: Sky::Gradient { horizon, zenith }, meshes: alloc::vec![dome] }
+    }
+
+    pub fn cube(faces: [SkyTexture; 6]) -> Self {
+        let layout = VertexLayout::new(Precision::Float).with_texture(Precision::Float);
+        let meshes = FACES.iter().map(|&(forward, up)| face(layout, forward, up)).collect();
+        Skybox { sky: Sky::Cube(faces), meshes }
+    }
+
+    pub fn dome(texture: SkyTexture) -> Self {
+        let layout = VertexLayout::new(Precision::Float).with_texture(Precision::Float);
+        Skybox { sky: Sky::Dome(texture), meshes: alloc::vec![dome(layout, |_| 0xFFFFFFFF)] }
+    }
+
+    /// Draw the sky around `eye`. Needs the model matrix mode active with view and projection set.
+    pub unsafe fn draw(&self, state: &mut RenderState, eye: &ScePspFVector3) {
+        sys::sceGumLoadIdentity();
+        sys::sceGumTranslate(eye);
+
+        // The camera is inside the sky, and nothing drawn later should be hidden by it
+        state.set(GuState::CullFace, false);
+        state.depth_write(false);
+        state.alpha_test(None);
+        state.blend(None);
+
+        let textures: &[SkyTexture] = match &self.sky {
+            Sky::Gradient { .. } => &[],
+            Sky::Cube(faces) => faces,
+            Sky::Dome(texture) => core::slice::from_ref(texture),
+        };
+
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            match textures.get(i) {
+                Some(texture) => {
+                    let Some(handle) = texture.handle.upgrade() else {
+                        state.stats.missing_textures += 1;
+                        continue;
+                    };
+                    state.set(GuState::Texture2D, true);
+                    state.bind_texture(&handle, texture.format, texture.swizzle);
+                    state.tex_map(None);
+                    state.tex_func(TextureEffect::Replace, TextureColorComponent::Rgb);
+                    state.tex_filter(true);
+                    state.tex_transform(1.0, 1.0, 0.0, 0.0);
+                }
+                None => state.set(GuState::Texture2D, false),
+            }
+
+            let indices = mesh.indices.as_ref().map_or(core::ptr::null(), |i| i.as_ptr() as *const _);
+            state.draw(
+                mesh.primitive_type,
+                mesh.vertex_type() | VertexType::TRANSFORM_3D,
+                mesh.draw_count(),
+                indices,
+                mesh.vertices.as_ptr() as *const _,
+            );
+        }
+
+        state.set(GuState::CullFace, true);
+        state.depth_write(true);
+    }
+}
+
+fn indexed(layout: VertexLayout, attributes: &[VertexAttributes], indices: &[u16]) -> Mesh {
+    Mesh {
+        indices: Some(AVec::<u16, ConstAlign<16>>::from_slice(16, indices)),
+        ..Mesh::from_attributes(layout, attributes)
+    }
+}
+
+/// Sphere with equirectangular UVs; `color` gets the sine of each vertex's elevation
+fn dome(layout: VertexLayout, color: impl Fn(f32) -> u32) -> Mesh {
+    let mut attributes = Vec::with_capacity((DOME_RINGS + 1) * (DOME_SEGMENTS + 1));
+    for ring in 0..=DOME_RINGS {
+        let v = ring as f32 / DOME_RINGS as f32;
+        let polar = v * PI;
+        let (y, r) = (psp_math::vfpu_cosf(polar), psp_math::vfpu_sinf(polar));
+
+        // The seam column is doubled so the texture can wrap all the way round
+        for segment in 0..=DOME_SEGMENTS {
+            let u = segment as f32 / DOME_SEGMENTS as f32;
+            let azimuth = u * 2.0 * PI;
+            attributes.push(VertexAttributes {
+                position: [
+                    r * psp_math::vfpu_sinf(azimuth) * SKY_RADIUS,
+                    y * SKY_RADIUS,
+                    -r * psp_math::vfpu_cosf(azimuth) * SKY_RADIUS,
+                ],
+                uv: [u, v],
+                color: color(y),
+                ..Default::default()
+            });
+        }
+    }
+
+    let mut indices = Vec::with_capacity(DOME_RINGS * DOME_SEGMENTS * 6);
+    let row = DOME_SEGMENTS as u16 + 1;
+    for ring in 0..DOME_RINGS as u16 {
+        for segment in 0..DOME_SEGMENTS as u16 {
+            let a = ring * row + segment;
+            let b = a + row;
+            indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+        }
+    }
+
+    indexed(layout, &attributes, &indices)
+}
+
+/// Inward facing quad for one side of the sky cube
+fn face(layout: VertexLayout, forward: [f32; 3], up: [f32; 3]) -> Mesh {
+    let right = psp_math::cross(forward, up);
+
+    let corner = |sx: f32, sy: f32| VertexAttributes {
+        position: [0, 1, 2].map(|i| (forward[i] + right[i] * sx + up[i] * sy) * SKY_RADIUS),
+        uv: [(sx + 1.0) * 0.5, (1.0 - sy) * 0.5],
+        ..Default::default()
+    };
+
+    let attributes = [corner(-1.0, 1.0), corner(1.0, 1.0), corner(1.0, -1.0), corner(-1.0, -1.0)];
+    indexed(layout, &attributes, &[0, 1, 2, 0, 2, 3])
+}
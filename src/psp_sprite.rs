@@ -0,0 +1,220 @@
+use core::ffi::c_void;
+use core::mem::size_of;
+
+use alloc::sync::{Arc, Weak};
+use bevy_ecs::component::Component;
+use psp::sys::{self, GuPrimitive, GuState, TexturePixelFormat, VertexType};
+
+use crate::psp_assets::TextureHandle;
+use crate::psp_math;
+use crate::psp_render::{DisplayLists, RenderState};
+
+/// A screen space image, drawn after the 3D pass. Sprites with a higher `z` are drawn on top.
+#[derive(Component, Clone)]
+pub struct Sprite {
+    /// `None` draws a solid rectangle in `color`
+    pub handle: Option<Weak<TextureHandle>>,
+    pub texture_format: TexturePixelFormat,
+    pub swizzle: bool,
+    /// Part of the texture to show as `[x, y, width, height]` in texels, or the whole texture
+    pub region: Option<[u16; 4]>,
+    /// Top left corner in pixels
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    /// Radians, clockwise around the center
+    pub rotation: f32,
+    /// ABGR, multiplied with the texture
+    pub color: u32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub z: i32,
+}
+
+/// Through mode vertex; positions are in pixels and texture coordinates in texels
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SpriteVertex {
+    u: i16,
+    v: i16,
+    color: u32,
+    x: i16,
+    y: i16,
+    z: i16,
+}
+
+impl Sprite {
+    /// Sprite showing the whole texture at its own size
+    pub fn new(handle: &Arc<TextureHandle>, texture_format: TexturePixelFormat, swizzle: bool) -> Self {
+        Sprite {
+            handle: Some(Arc::downgrade(handle)),
+            texture_format,
+            swizzle,
+            region: None,
+            position: [0.0, 0.0],
+            size: [handle.width() as f32, handle.height() as f32],
+            rotation: 0.0,
+            color: 0xFFFFFFFF,
+            flip_x: false,
+            flip_y: false,
+            z: 0,
+        }
+    }
+
+    /// Untextured rectangle, e.g. for bars and panels
+    pub fn solid(color: u32, width: f32, height: f32) -> Self {
+        Sprite {
+            handle: None,
+            texture_format: TexturePixelFormat::Psm8888,
+            swizzle: false,
+            region: None,
+            position: [0.0, 0.0],
+            size: [width, height],
+            rotation: 0.0,
+            color,
+            flip_x: false,
+            flip_y: false,
+            z: 0,
+        }
+    }
+
+    /// Show part of an atlas; also resizes the sprite to match the region
+    pub fn with_region(mut self, x: u16, y: u16, width: u16, height: u16) -> Self {
+        self.region = Some([x, y, width, height]);
+        self.size = [width as f32, height as f32];
+        self
+    }
+
+    pub fn with_position(mut self, x: f32, y: f32) -> Self {
+        self.position = [x, y];
+        self
+    }
+
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.size = [width, height];
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_color(mut self, color: u32) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_flip(mut self, flip_x: bool, flip_y: bool) -> Self {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self
+    }
+
+    pub fn with_z(mut self, z: i32) -> Self {
+        self.z = z;
+        self
+    }
+
+    /// Unrotated sprites go out as `Sprites` rectangles, rotated ones need two triangles
+    fn primitive(&self) -> GuPrimitive {
+        if self.rotation == 0.0 { GuPrimitive::Sprites } else { GuPrimitive::Triangles }
+    }
+
+    fn vertex_count(&self) -> usize {
+        if self.rotation == 0.0 { 2 } else { 6 }
+    }
+
+    /// Texture corners as `[u0, v0, u1, v1]` after flipping
+    fn uvs(&self, texture: Option<&TextureHandle>) -> [i16; 4] {
+        let [x, y, w, h] = match (self.region, texture) {
+            (Some(region), _) => region,
+            (None, Some(texture)) => [0, 0, texture.width() as u16, texture.height() as u16],
+            (None, None) => [0, 0, 0, 0],
+        };
+
+        let (mut u0, mut u1) = (x as i16, (x + w) as i16);
+        let (mut v0, mut v1) = (y as i16, (y + h) as i16);
+        if self.flip_x {
+            core::mem::swap(&mut u0, &mut u1);
+        }
+        if self.flip_y {
+            core::mem::swap(&mut v0, &mut v1);
+        }
+        [u0, v0, u1, v1]
+    }
+
+    /// Write this sprite's vertices to `out`, which has room for `vertex_count` of them
+    unsafe fn write(&self, texture: Option<&TextureHandle>, out: *mut SpriteVertex) {
+        let [u0, v0, u1, v1] = self.uvs(texture);
+        let vertex = |u, v, x: f32, y: f32| SpriteVertex { u, v, color: self.color, x: x as i16, y: y as i16, z: 0 };
+
+        let [x, y] = self.position;
+        let [w, h] = self.size;
+        if self.rotation == 0.0 {
+            out.write(vertex(u0, v0, x, y));
+            out.add(1).write(vertex(u1, v1, x + w, y + h));
+            return;
+        }
+
+        let sin = psp_math::vfpu_sinf(self.rotation);
+        let cos = psp_math::vfpu_cosf(self.rotation);
+        let (cx, cy) = (x + w * 0.5, y + h * 0.5);
+        let corner = |u, v, dx: f32, dy: f32| vertex(u, v, cx + dx * cos - dy * sin, cy + dx * sin + dy * cos);
+
+        let (hw, hh) = (w * 0.5, h * 0.5);
+        let corners = [
+            corner(u0, v0, -hw, -hh),
+            corner(u1, v0, hw, -hh),
+            corner(u1, v1, hw, hh),
+            corner(u0, v1, -hw, hh),
+        ];
+        for (i, &c) in [0, 1, 2, 0, 2, 3].iter().enumerate() {
+            out.add(i).write(corners[c]);
+        }
+    }
+
+    /// Sprites sharing a key can be drawn in the same call
+    fn batch_key(&self) -> (usize, bool) {
+        let texture = self.handle.as_ref().map_or(0, |h| h.as_ptr() as usize);
+        (texture, self.rotation == 0.0)
+    }
+}
+
+/// Draw `sprites` in order, batching runs that share a texture and primitive into one draw call.
+/// Expects 2D state from `begin_2d`. Vertices live in the display list.
+pub unsafe fn draw_sprites(state: &mut RenderState, lists: &mut DisplayLists, sprites: &[&Sprite]) {
+    for batch in sprites.chunk_by(|a, b| a.batch_key() == b.batch_key()) {
+        let first = batch[0];
+        let texture = match &first.handle {
+            Some(handle) => match handle.upgrade() {
+                Some(texture) => Some(texture),
+                None => {
+                    state.stats.missing_textures += 1;
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        let count = batch.iter().map(|s| s.vertex_count()).sum::<usize>();
+        let bytes = count * size_of::<SpriteVertex>();
+        lists.reserve(bytes.div_ceil(4) + 32);
+
+        let vertices = sys::sceGuGetMemory(bytes as i32) as *mut SpriteVertex;
+        let mut written = 0;
+        for sprite in batch {
+            sprite.write(texture.as_deref(), vertices.add(written));
+            written += sprite.vertex_count();
+        }
+
+        match &texture {
+            Some(texture) => {
+                state.set(GuState::Texture2D, true);
+                state.bind_texture(texture, first.texture_format, first.swizzle);
+            }
+            None => state.set(GuState::Texture2D, false),
+        }
+        let vtype = VertexType::TEXTURE_16BIT | VertexType::COLOR_8888 | VertexType::VERTEX_16BIT | VertexType::TRANSFORM_2D;
+        state.draw(first.primitive(), vtype, count, core::ptr::null(), vertices as *const c_void);
+    }
+}
@@ -1 +1,349 @@
+use core::ffi::c_void;
+use core::mem::size_of;
 
+use alloc::{format, string::String, sync::{Arc, Weak}, vec::Vec};
+use bevy_ecs::component::Component;
+use hashbrown::HashMap;
+use psp::sys::{self, GuPrimitive, GuState, TexturePixelFormat, VertexType};
+
+use crate::psp_assets::{read_file, IoError, TextureHandle};
+use crate::psp_render::RenderState;
+
+/// Where a single character sits in the font texture, in texels
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Glyph {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    /// Offset from the pen position to the top left of the glyph
+    pub x_offset: i16,
+    pub y_offset: i16,
+    /// How far the pen moves after drawing this glyph
+    pub advance: u16,
+}
+
+/// Glyph layout of a bitmap font, independent of the texture it samples from
+#[derive(Clone, Debug, Default)]
+pub struct FontMetrics {
+    pub line_height: u16,
+    glyphs: HashMap<char, Glyph>,
+    /// Used for characters the font doesn't have
+    fallback: Option<Glyph>,
+}
+
+impl FontMetrics {
+    /// Fixed width font laid out as a grid of equally sized cells, starting at `first` and going
+    /// left to right, top to bottom
+    pub fn grid(first: char, columns: u16, rows: u16, cell_width: u16, cell_height: u16) -> Self {
+        let mut glyphs = HashMap::new();
+        for i in 0..columns as u32 * rows as u32 {
+            let Some(c) = char::from_u32(first as u32 + i) else {
+                continue;
+            };
+            glyphs.insert(c, Glyph {
+                x: (i % columns as u32) as u16 * cell_width,
+                y: (i / columns as u32) as u16 * cell_height,
+                width: cell_width,
+                height: cell_height,
+                x_offset: 0,
+                y_offset: 0,
+                advance: cell_width,
+            });
+        }
+
+        let fallback = glyphs.get(&'?').copied();
+        FontMetrics { line_height: cell_height, glyphs, fallback }
+    }
+
+    /// Parse the text variant of an AngelCode BMFont descriptor. Only single page fonts are
+    /// supported.
+    pub fn from_bmfont(descriptor: &'_ str) -> Result<Self, IoError> {
+        let mut metrics = FontMetrics::default();
+
+        for line in descriptor.lines() {
+            let mut words = line.split_whitespace();
+            let tag = words.next().unwrap_or("");
+            let value = |key: &str| -> i32 {
+                line.split_whitespace()
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(k, _)| *k == key)
+                    .and_then(|(_, v)| v.parse().ok())
+                    .unwrap_or(0)
+            };
+
+            match tag {
+                "common" => {
+                    metrics.line_height = value("lineHeight") as u16;
+                    if value("pages") > 1 {
+                        return Err(IoError(format!("Multi-page BMFont descriptors are not supported")));
+                    }
+                }
+                "char" => {
+                    let Some(c) = char::from_u32(value("id") as u32) else {
+                        continue;
+                    };
+                    metrics.glyphs.insert(c, Glyph {
+                        x: value("x") as u16,
+                        y: value("y") as u16,
+                        width: value("width") as u16,
+                        height: value("height") as u16,
+                        x_offset: value("xoffset") as i16,
+                        y_offset: value("yoffset") as i16,
+                        advance: value("xadvance") as u16,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if metrics.glyphs.is_empty() {
+            return Err(IoError(format!("BMFont descriptor has no characters")));
+        }
+
+        metrics.fallback = metrics.glyphs.get(&'?').copied();
+        Ok(metrics)
+    }
+
+    /// Load a BMFont text descriptor from disk
+    pub fn load_bmfont(path: &'_ str) -> Result<Self, IoError> {
+        let bytes = read_file(path)?;
+        let text = core::str::from_utf8(&bytes).map_err(|_| IoError(format!("\"{}\" is not valid UTF-8", path)))?;
+        FontMetrics::from_bmfont(text)
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or(self.fallback.as_ref())
+    }
+
+    /// Horizontal advance of a single character, unscaled
+    pub fn advance(&self, c: char) -> f32 {
+        self.glyph(c).map_or(0.0, |g| g.advance as f32)
+    }
+
+    /// Width of a single line, ignoring newlines and wrapping
+    pub fn line_width(&self, line: &'_ str, scale: f32) -> f32 {
+        line.chars().map(|c| self.advance(c)).sum::<f32>() * scale
+    }
+
+    /// Break `text` into lines at newlines and, if `wrap_width` is set, at the last space that
+    /// keeps each line inside it. Words longer than the wrap width are split mid-word.
+    pub fn lines<'t>(&self, text: &'t str, scale: f32, wrap_width: Option<f32>) -> Vec<(&'t str, f32)> {
+        let mut lines = Vec::new();
+
+        for paragraph in text.split('\n') {
+            let Some(wrap) = wrap_width else {
+                lines.push((paragraph, self.line_width(paragraph, scale)));
+                continue;
+            };
+
+            let space = self.advance(' ') * scale;
+            let mut start = 0;
+            let mut width = 0.0;
+            // Byte index of the last space on this line, and the line width up to it
+            let mut last_break: Option<(usize, f32)> = None;
+
+            for (i, c) in paragraph.char_indices() {
+                let advance = self.advance(c) * scale;
+                if c == ' ' {
+                    last_break = Some((i, width));
+                } else if width + advance > wrap && i > start {
+                    match last_break.take() {
+                        Some((at, line_width)) => {
+                            lines.push((&paragraph[start..at], line_width));
+                            start = at + 1;
+                            width -= line_width + space;
+                        }
+                        None => {
+                            lines.push((&paragraph[start..i], width));
+                            start = i;
+                            width = 0.0;
+                        }
+                    }
+                }
+                width += advance;
+            }
+            lines.push((&paragraph[start..], width));
+        }
+
+        lines
+    }
+
+    /// Size of the box `text` occupies once laid out
+    pub fn measure(&self, text: &'_ str, scale: f32, wrap_width: Option<f32>) -> (f32, f32) {
+        let lines = self.lines(text, scale, wrap_width);
+        let width = lines.iter().fold(0.0f32, |w, (_, line)| w.max(*line));
+        (width, lines.len() as f32 * self.line_height as f32 * scale)
+    }
+}
+
+/// A font texture together with its glyph layout
+#[derive(Clone)]
+pub struct TextFont {
+    pub handle: Weak<TextureHandle>,
+    pub texture_format: TexturePixelFormat,
+    pub swizzle: bool,
+    pub metrics: Arc<FontMetrics>,
+}
+
+impl TextFont {
+    pub fn new(handle: &Arc<TextureHandle>, texture_format: TexturePixelFormat, swizzle: bool, metrics: FontMetrics) -> Self {
+        TextFont {
+            handle: Arc::downgrade(handle),
+            texture_format,
+            swizzle,
+            metrics: Arc::new(metrics),
+        }
+    }
+}
+
+/// Which side of a line the text position refers to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// A block of screen space text. Position is the top of the first line in pixels, and horizontally
+/// either the left edge, center or right edge depending on `align`.
+#[derive(Component, Clone)]
+pub struct Text {
+    pub value: String,
+    pub font: TextFont,
+    pub position: [f32; 2],
+    /// ABGR, multiplied with the font texture
+    pub color: u32,
+    pub scale: f32,
+    pub align: TextAlign,
+    pub wrap_width: Option<f32>,
+}
+
+/// Sprite corner in through mode, where texture coordinates are in texels and positions in pixels
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GlyphVertex {
+    u: i16,
+    v: i16,
+    x: i16,
+    y: i16,
+    z: i16,
+}
+
+impl Text {
+    pub fn new(value: impl Into<String>, font: &TextFont) -> Self {
+        Text {
+            value: value.into(),
+            font: font.clone(),
+            position: [0.0, 0.0],
+            color: 0xFFFFFFFF,
+            scale: 1.0,
+            align: TextAlign::Left,
+            wrap_width: None,
+        }
+    }
+
+    pub fn with_position(mut self, x: f32, y: f32) -> Self {
+        self.position = [x, y];
+        self
+    }
+
+    pub fn with_color(mut self, color: u32) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_wrap_width(mut self, width: f32) -> Self {
+        self.wrap_width = Some(width);
+        self
+    }
+
+    /// Size of the laid out text in pixels
+    pub fn measure(&self) -> (f32, f32) {
+        self.font.metrics.measure(&self.value, self.scale, self.wrap_width)
+    }
+
+    /// Upper bound on the display list space `draw` needs, in words
+    pub fn list_words(&self) -> usize {
+        let vertices = self.value.len() * 2 * size_of::<GlyphVertex>();
+        vertices.div_ceil(4) + 32
+    }
+
+    /// Draw every glyph as a single batch of sprites. Expects the caller to have set up 2D state
+    /// (see `begin_2d`). Vertices live in the display list, so `list_words` of space has to be
+    /// reserved first.
+    pub unsafe fn draw(&self, state: &mut RenderState) {
+        let Some(texture) = self.font.handle.upgrade() else {
+            state.stats.missing_textures += 1;
+            return;
+        };
+
+        let metrics = &self.font.metrics;
+        let lines = metrics.lines(&self.value, self.scale, self.wrap_width);
+        let glyphs = lines.iter().map(|(line, _)| line.chars().count()).sum::<usize>();
+        if glyphs == 0 {
+            return;
+        }
+
+        let vertices = sys::sceGuGetMemory((glyphs * 2 * size_of::<GlyphVertex>()) as i32) as *mut GlyphVertex;
+        let mut count = 0;
+
+        let line_height = metrics.line_height as f32 * self.scale;
+        for (row, (line, width)) in lines.iter().enumerate() {
+            let mut pen = match self.align {
+                TextAlign::Left => self.position[0],
+                TextAlign::Center => self.position[0] - width * 0.5,
+                TextAlign::Right => self.position[0] - width,
+            };
+            let top = self.position[1] + row as f32 * line_height;
+
+            for c in line.chars() {
+                let Some(glyph) = metrics.glyph(c) else {
+                    continue;
+                };
+
+                if glyph.width > 0 && glyph.height > 0 {
+                    let x = pen + glyph.x_offset as f32 * self.scale;
+                    let y = top + glyph.y_offset as f32 * self.scale;
+                    vertices.add(count).write(GlyphVertex {
+                        u: glyph.x as i16,
+                        v: glyph.y as i16,
+                        x: x as i16,
+                        y: y as i16,
+                        z: 0,
+                    });
+                    vertices.add(count + 1).write(GlyphVertex {
+                        u: (glyph.x + glyph.width) as i16,
+                        v: (glyph.y + glyph.height) as i16,
+                        x: (x + glyph.width as f32 * self.scale) as i16,
+                        y: (y + glyph.height as f32 * self.scale) as i16,
+                        z: 0,
+                    });
+                    count += 2;
+                }
+                pen += glyph.advance as f32 * self.scale;
+            }
+        }
+
+        if count == 0 {
+            return;
+        }
+
+        state.set(GuState::Texture2D, true);
+        state.bind_texture(&texture, self.font.texture_format, self.font.swizzle);
+        state.color(self.color);
+        let vtype = VertexType::TEXTURE_16BIT | VertexType::VERTEX_16BIT | VertexType::TRANSFORM_2D;
+        state.draw(GuPrimitive::Sprites, vtype, count, core::ptr::null(), vertices as *const c_void);
+    }
+}
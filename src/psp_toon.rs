@@ -0,0 +1,102 @@
+use aligned_vec::{AVec, ConstAlign};
+use alloc::sync::Arc;
+use psp::sys::{self, FrontFaceDirection, GuState, LightComponent, LightType, ScePspFVector3, TextureColorComponent, TextureEffect, TexturePixelFormat, VertexType};
+
+use crate::psp_assets::TextureHandle;
+use crate::psp_geometry::Mesh;
+use crate::psp_render::RenderState;
+
+/// Texels across a generated ramp
+const RAMP_WIDTH: usize = 16;
+
+/// Light whose position becomes the ramp coordinate of toon materials. Never enabled, so it
+/// doesn't light anything by itself.
+pub const TOON_LIGHT: u32 = 0;
+
+/// Back faces of the mesh, grown by `width` and drawn in `color` behind it
+#[derive(Clone, Copy, Debug)]
+pub struct Outline {
+    pub width: f32,
+    pub color: u32,
+}
+
+/// Cel shading: the material color is multiplied by a ramp texture looked up by how directly each
+/// vertex faces the primary `DirectionalLight`. The ramp takes the GE's only texture, so toon
+/// materials aren't textured otherwise. Needs a mesh with normals.
+#[derive(Clone, Debug)]
+pub struct Toon {
+    /// Read left (facing away from the light) to right (facing it)
+    pub ramp: Arc<TextureHandle>,
+    pub outline: Option<Outline>,
+}
+
+impl Toon {
+    pub fn new(ramp: Arc<TextureHandle>) -> Self {
+        Toon { ramp, outline: None }
+    }
+
+    pub fn with_outline(mut self, width: f32, color: u32) -> Self {
+        self.outline = Some(Outline { width, color });
+        self
+    }
+}
+
+/// A ramp with hard steps between `shades`, darkest first. Each shade gets an equal share.
+pub fn ramp(shades: &[u32]) -> Arc<TextureHandle> {
+    let mut pixels = AVec::<u8, ConstAlign<16>>::with_capacity(16, RAMP_WIDTH * 4);
+    for i in 0..RAMP_WIDTH {
+        let shade = shades.get(i * shades.len() / RAMP_WIDTH).copied().unwrap_or(0xFFFFFFFF);
+        pixels.extend_from_slice(&shade.to_le_bytes());
+    }
+    Arc::new(TextureHandle::new(RAMP_WIDTH, 1, RAMP_WIDTH, pixels))
+}
+
+/// Point the ramp coordinate at the light. `direction` is where the light travels.
+pub unsafe fn set_light(direction: [f32; 3]) {
+    let towards = ScePspFVector3 { x: -direction[0], y: -direction[1], z: -direction[2] };
+    sys::sceGuLight(TOON_LIGHT as i32, LightType::Directional, LightComponent::DIFFUSE, &towards);
+}
+
+/// Set up the ramp for a toon material drawn in `color`
+pub fn bind(state: &mut RenderState, toon: &Toon, color: u32) {
+    state.set(GuState::Texture2D, true);
+    state.bind_texture(&toon.ramp, TexturePixelFormat::Psm8888, false);
+    state.tex_map(Some((TOON_LIGHT, TOON_LIGHT)));
+    // Repeating would wrap the lit end of the ramp back round to the dark end
+    state.tex_wrap(false);
+    state.tex_func(TextureEffect::Modulate, TextureColorComponent::Rgb);
+    state.color(color);
+}
+
+/// Draw the inverted hull outline around a mesh that was just drawn. Expects the model matrix
+/// for the mesh already loaded, minus its scale.
+pub unsafe fn draw_outline(state: &mut RenderState, outline: &Outline, mesh: &Mesh, indices: *const core::ffi::c_void) {
+    // Vertex colors would override the outline color
+    if mesh.layout.color.is_some() || mesh.sphere.radius <= 0.0 {
+        return;
+    }
+
+    // Grow the mesh around its center, so the hull sticks out by about `width` all round
+    let grow = 1.0 + outline.width / mesh.sphere.radius;
+    let [cx, cy, cz] = mesh.sphere.center;
+    sys::sceGumTranslate(&ScePspFVector3 { x: cx, y: cy, z: cz });
+    sys::sceGumScale(&ScePspFVector3 { x: grow, y: grow, z: grow });
+    sys::sceGumTranslate(&ScePspFVector3 { x: -cx, y: -cy, z: -cz });
+    if mesh.scale != 1.0 {
+        sys::sceGumScale(&ScePspFVector3 { x: mesh.scale, y: mesh.scale, z: mesh.scale });
+    }
+
+    state.set(GuState::Texture2D, false);
+    state.color(outline.color);
+
+    // Only the far side of the hull is drawn, so the mesh covers all of it but the rim
+    sys::sceGuFrontFace(FrontFaceDirection::CounterClockwise);
+    state.draw(
+        mesh.primitive_type,
+        mesh.vertex_type() | VertexType::TRANSFORM_3D,
+        mesh.draw_count(),
+        indices,
+        mesh.vertices.as_ptr() as *const _,
+    );
+    sys::sceGuFrontFace(FrontFaceDirection::Clockwise);
+}